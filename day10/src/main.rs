@@ -1,11 +1,13 @@
 mod point;
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
 
 use point::Point;
 
 type Direction = (i32, i32);
-type Path = Vec<Point>;
 
 const UP: Direction = (-1, 0);
 const DOWN: Direction = (1, 0);
@@ -14,24 +16,58 @@ const RIGHT: Direction = (0, 1);
 
 const DIRECTIONS: [Direction; 4] = [UP, DOWN, LEFT, RIGHT];
 
+// What counts as a trail: where it starts and ends, and which changes in
+// height are allowed from one step to the next.
+struct Trail {
+    start: u32,
+    summit: u32,
+    steps: RangeInclusive<i32>,
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Trail {
+            start: 0,
+            summit: 9,
+            steps: 1..=1,
+        }
+    }
+}
+
+impl Trail {
+    // Trails that always climb (or always descend) can never revisit a
+    // point, so the counts for each point can be memoised.
+    fn is_acyclic(&self) -> bool {
+        *self.steps.start() > 0 || *self.steps.end() < 0
+    }
+}
+
 struct Topograph {
-    grid: Vec<Vec<u32>>,
+    // Impassable points have no height.
+    grid: Vec<Vec<Option<u32>>>,
+    trail: Trail,
     width: usize,
     height: usize,
+    // Memoised per point: the summits it can reach and how many trails lead there.
+    summits: RefCell<HashMap<Point, Rc<HashSet<Point>>>>,
+    ratings: RefCell<HashMap<Point, u64>>,
 }
 
 impl Topograph {
-    fn new(grid: Vec<Vec<u32>>) -> Topograph {
+    fn new(grid: Vec<Vec<Option<u32>>>, trail: Trail) -> Topograph {
         Topograph {
             width: grid[0].len(),
             height: grid.len(),
             grid,
+            trail,
+            summits: RefCell::new(HashMap::new()),
+            ratings: RefCell::new(HashMap::new()),
         }
     }
 
     fn at(&self, point: &Point) -> Option<u32> {
         if self.is_valid(point) {
-            Some(self.grid[point.row as usize][point.col as usize])
+            self.grid[point.row as usize][point.col as usize]
         } else {
             None
         }
@@ -47,31 +83,178 @@ impl Topograph {
             .flat_map(|row| {
                 (0..self.width)
                     .map(move |col| Point::new(row as i32, col as i32))
-                    .filter(|point| self.at(point) == Some(0))
+                    .filter(|point| self.at(point) == Some(self.trail.start))
             })
             .collect()
     }
 
-    fn find_walked_trails(&self, trailhead: Point) -> HashSet<Path> {
-        let mut paths: Vec<Path> = Vec::new();
-        let mut walked: HashSet<Path> = HashSet::new();
-        paths.push(vec![trailhead]);
+    // The number of distinct summits reachable from the trailhead.
+    fn score(&self, trailhead: Point) -> usize {
+        if self.trail.is_acyclic() {
+            self.reachable_summits(trailhead).len()
+        } else {
+            self.search_summits(trailhead).len()
+        }
+    }
+
+    // The number of distinct trails from the trailhead to any summit.
+    fn rating(&self, trailhead: Point) -> u64 {
+        if self.trail.is_acyclic() {
+            self.count_trails(trailhead)
+        } else {
+            let mut visited = vec![vec![false; self.width]; self.height];
+            self.count_simple_trails(trailhead, &mut visited)
+        }
+    }
+
+    fn count_trails(&self, trailhead: Point) -> u64 {
+        if let Some(rating) = self.ratings.borrow().get(&trailhead) {
+            return *rating;
+        }
+        let rating = if self.is_summit(trailhead) {
+            1
+        } else {
+            self.find_next(trailhead)
+                .into_iter()
+                .map(|next| self.count_trails(next))
+                .sum()
+        };
+        self.ratings.borrow_mut().insert(trailhead, rating);
+        rating
+    }
+
+    fn reachable_summits(&self, point: Point) -> Rc<HashSet<Point>> {
+        if let Some(summits) = self.summits.borrow().get(&point) {
+            return summits.clone();
+        }
+        let summits = if self.is_summit(point) {
+            HashSet::from([point])
+        } else {
+            let mut summits = HashSet::new();
+            for next in self.find_next(point) {
+                summits.extend(self.reachable_summits(next).iter());
+            }
+            summits
+        };
+        let summits = Rc::new(summits);
+        self.summits.borrow_mut().insert(point, summits.clone());
+        summits
+    }
+
+    // Every point reachable from the trailhead. Summits end a trail.
+    fn reachable(&self, trailhead: Point) -> HashSet<Point> {
+        let mut seen = HashSet::from([trailhead]);
+        let mut queue = vec![trailhead];
+        while let Some(point) = queue.pop() {
+            if self.is_summit(point) {
+                continue;
+            }
+            for next in self.find_next(point) {
+                if seen.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+        seen
+    }
+
+    // When a trail may return to the same height it can loop, so walk the
+    // map without memoising.
+    fn search_summits(&self, trailhead: Point) -> HashSet<Point> {
+        self.reachable(trailhead)
+            .into_iter()
+            .filter(|point| self.is_summit(*point))
+            .collect()
+    }
+
+    // How many of the trailhead's trails pass through each point.
+    fn heat_map(&self, trailhead: Point) -> Vec<Vec<u64>> {
+        let mut heat = vec![vec![0; self.width]; self.height];
+        if !self.trail.is_acyclic() {
+            let mut visited = vec![vec![false; self.width]; self.height];
+            let mut path = Vec::new();
+            self.mark_simple_trails(trailhead, &mut path, &mut visited, &mut heat);
+            return heat;
+        }
+        // Trails through a point = trails reaching it from the trailhead x
+        // trails from it to a summit. Heights only move one way, so visiting
+        // points in height order sees every route into a point before leaving it.
+        let mut points: Vec<Point> = self.reachable(trailhead).into_iter().collect();
+        points.sort_by_key(|point| self.at(point));
+        if *self.trail.steps.end() < 0 {
+            points.reverse();
+        }
+        let mut arrivals: HashMap<Point, u64> = HashMap::from([(trailhead, 1)]);
+        for point in points {
+            let count = arrivals.get(&point).copied().unwrap_or(0);
+            heat[point.row as usize][point.col as usize] = count * self.count_trails(point);
+            if !self.is_summit(point) {
+                for next in self.find_next(point) {
+                    *arrivals.entry(next).or_insert(0) += count;
+                }
+            }
+        }
+        heat
+    }
 
-        while let Some(path) = paths.pop() {
-            let last = path.last().unwrap();
-            let neighbours = self.find_next(*last);
+    fn mark_simple_trails(
+        &self,
+        point: Point,
+        path: &mut Vec<Point>,
+        visited: &mut Vec<Vec<bool>>,
+        heat: &mut [Vec<u64>],
+    ) {
+        path.push(point);
+        if self.is_summit(point) {
+            for p in path.iter() {
+                heat[p.row as usize][p.col as usize] += 1;
+            }
+        } else {
+            visited[point.row as usize][point.col as usize] = true;
+            for next in self.find_next(point) {
+                if !visited[next.row as usize][next.col as usize] {
+                    self.mark_simple_trails(next, path, visited, heat);
+                }
+            }
+            visited[point.row as usize][point.col as usize] = false;
+        }
+        path.pop();
+    }
 
-            for neighbour in neighbours {
-                let mut new_path = path.clone();
-                new_path.push(neighbour);
-                if self.reached_summit(&new_path) {
-                    walked.insert(new_path);
-                } else {
-                    paths.push(new_path);
+    // Draws the map with busier points in brighter red.
+    fn render(&self, heat: &[Vec<u64>]) -> String {
+        let hottest = heat.iter().flatten().copied().max().unwrap_or(0).max(1);
+        let mut output = String::new();
+        for (row, heights) in self.grid.iter().enumerate() {
+            for (col, height) in heights.iter().enumerate() {
+                let ch = height.map_or('.', |h| char::from_digit(h, 10).unwrap_or('+'));
+                match heat[row][col] {
+                    0 => output.push(ch),
+                    count => {
+                        let red = 80 + 175 * count / hottest;
+                        output += &format!("\x1b[48;2;{red};0;0m\x1b[97m{ch}\x1b[0m");
+                    }
                 }
             }
+            output.push('\n');
         }
-        walked
+        output
+    }
+
+    // Counts trails that never visit the same point twice.
+    fn count_simple_trails(&self, point: Point, visited: &mut Vec<Vec<bool>>) -> u64 {
+        if self.is_summit(point) {
+            return 1;
+        }
+        visited[point.row as usize][point.col as usize] = true;
+        let mut count = 0;
+        for next in self.find_next(point) {
+            if !visited[next.row as usize][next.col as usize] {
+                count += self.count_simple_trails(next, visited);
+            }
+        }
+        visited[point.row as usize][point.col as usize] = false;
+        count
     }
 
     // Finds the next points on the trail if any
@@ -82,7 +265,7 @@ impl Topograph {
         DIRECTIONS.iter().for_each(|(row, col)| {
             let neighbour = point + Point::new(*row, *col);
             if let Some(val) = self.at(&neighbour) {
-                if val == current + 1 {
+                if self.trail.steps.contains(&(val as i32 - current as i32)) {
                     output.push(neighbour);
                 }
             }
@@ -90,40 +273,156 @@ impl Topograph {
         output
     }
 
-    fn reached_summit(&self, path: &Path) -> bool {
-        let last = path.last().unwrap();
-        self.at(last) == Some(9)
+    fn is_summit(&self, point: Point) -> bool {
+        self.at(&point) == Some(self.trail.summit)
+    }
+}
+
+fn report(topo: &Topograph) {
+    for trailhead in topo.find_trailheads() {
+        println!(
+            "Trailhead {}: score {}, rating {}",
+            trailhead,
+            topo.score(trailhead),
+            topo.rating(trailhead)
+        );
     }
 }
 
 fn solve_part1(topo: &Topograph) -> usize {
     topo.find_trailheads()
         .into_iter()
-        .map(|trailhead| {
-            topo.find_walked_trails(trailhead)
-                .into_iter()
-                .map(|path| *path.last().unwrap())
-                .collect::<HashSet<Point>>()
-                .len()
-        })
+        .map(|trailhead| topo.score(trailhead))
         .sum()
 }
 
-fn solve_part2(topo: &Topograph) -> usize {
+fn solve_part2(topo: &Topograph) -> u64 {
     topo.find_trailheads()
         .into_iter()
-        .map(|trailhead| topo.find_walked_trails(trailhead).len())
+        .map(|trailhead| topo.rating(trailhead))
         .sum()
 }
 
-fn main() {
-    let grid: Vec<Vec<u32>> = include_str!("puzzle.txt")
+// Removes `option` and the value following it from args.
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+fn parse_height(ch: char) -> Option<u32> {
+    match ch {
+        '.' => None,
+        _ => Some(ch.to_digit(10).unwrap()),
+    }
+}
+
+fn parse_grid(input: &str) -> Vec<Vec<Option<u32>>> {
+    input
         .lines()
-        .map(|line| line.chars().map(|ch| ch.to_digit(10).unwrap()).collect())
-        .collect();
+        .map(|line| line.chars().map(parse_height).collect())
+        .collect()
+}
+
+fn main() {
+    // Options: [--input example|impassable] [--start H] [--summit H] [--steps MIN,MAX]
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = match take_option(&mut args, "--input").as_deref() {
+        Some("example") => include_str!("example.txt"),
+        Some("impassable") => include_str!("impassable.txt"),
+        _ => include_str!("puzzle.txt"),
+    };
+    let mut trail = Trail::default();
+    if let Some(start) = take_option(&mut args, "--start") {
+        trail.start = start.parse().unwrap();
+    }
+    if let Some(summit) = take_option(&mut args, "--summit") {
+        trail.summit = summit.parse().unwrap();
+    }
+    if let Some(steps) = take_option(&mut args, "--steps") {
+        let (min, max) = steps.split_once(',').unwrap();
+        trail.steps = min.parse().unwrap()..=max.parse().unwrap();
+    }
 
-    let topo = Topograph::new(grid);
+    let topo = Topograph::new(parse_grid(input), trail);
+
+    // cargo run -- [options] report
+    // cargo run -- [options] render <row> <col>
+    match args.first().map(String::as_str) {
+        Some("report") => return report(&topo),
+        Some("render") => {
+            let trailhead = Point::new(args[1].parse().unwrap(), args[2].parse().unwrap());
+            print!("{}", topo.render(&topo.heat_map(trailhead)));
+            return;
+        }
+        _ => {}
+    }
 
     println!("Part 1: {:?}", solve_part1(&topo));
     println!("Part 2: {:?}", solve_part2(&topo));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Topograph {
+        Topograph::new(parse_grid(include_str!("example.txt")), Trail::default())
+    }
+
+    #[test]
+    fn example_answers() {
+        let topo = example();
+        assert_eq!(solve_part1(&topo), 36);
+        assert_eq!(solve_part2(&topo), 81);
+    }
+
+    // The memoised counts should agree with walking every trail afresh.
+    #[test]
+    fn memoised_counts_match_search() {
+        let topo = example();
+        for trailhead in topo.find_trailheads() {
+            assert_eq!(topo.score(trailhead), topo.search_summits(trailhead).len());
+            let mut visited = vec![vec![false; topo.width]; topo.height];
+            assert_eq!(
+                topo.rating(trailhead),
+                topo.count_simple_trails(trailhead, &mut visited)
+            );
+        }
+    }
+
+    #[test]
+    fn impassable_points_block_trails() {
+        let topo = Topograph::new(parse_grid(include_str!("impassable.txt")), Trail::default());
+        assert_eq!(solve_part1(&topo), 4);
+        assert_eq!(solve_part2(&topo), 13);
+    }
+
+    // Walking down from each summit finds the same trails as walking up.
+    #[test]
+    fn descending_trails() {
+        let trail = Trail {
+            start: 9,
+            summit: 0,
+            steps: -1..=-1,
+        };
+        let topo = Topograph::new(parse_grid(include_str!("example.txt")), trail);
+        assert_eq!(solve_part1(&topo), 36);
+        assert_eq!(solve_part2(&topo), 81);
+    }
+
+    #[test]
+    fn trails_that_climb_two_at_a_time() {
+        // 0 -> 2 -> 3 and 0 -> 1 -> 3 both need a step of two.
+        let grid = || parse_grid("02\n13\n");
+        let summit = |steps| Trail {
+            summit: 3,
+            steps,
+            ..Trail::default()
+        };
+        let topo = Topograph::new(grid(), summit(1..=2));
+        assert_eq!((solve_part1(&topo), solve_part2(&topo)), (1, 2));
+        let topo = Topograph::new(grid(), summit(1..=1));
+        assert_eq!((solve_part1(&topo), solve_part2(&topo)), (0, 0));
+    }
+}
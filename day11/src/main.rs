@@ -1,61 +1,388 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 // const INPUT: &str = "125 17";
 const INPUT: &str = "0 89741 316108 7641 756 9 7832357 91";
 
+// Build with `--features u128` for stones and counts too large for u64.
+#[cfg(not(feature = "u128"))]
 type Stone = u64;
+#[cfg(not(feature = "u128"))]
+type Count = u64;
+#[cfg(feature = "u128")]
+type Stone = u128;
+#[cfg(feature = "u128")]
+type Count = u128;
 
-fn blink(num: Stone) -> Vec<Stone> {
-    if num == 0 {
-        return vec![1];
+type Predicate = Box<dyn Fn(Stone) -> bool>;
+// Returns None when the new stones would overflow.
+type Transform = Box<dyn Fn(Stone) -> Option<Vec<Stone>>>;
+
+#[derive(Debug)]
+enum Overflow {
+    Stone { blink: u32, stone: Stone },
+    Count { blink: u32 },
+}
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Overflow::Stone { blink, stone } => {
+                write!(f, "blink {}: stone {} overflowed", blink, stone)
+            }
+            Overflow::Count { blink } => write!(f, "blink {}: stone count overflowed", blink),
+        }
+    }
+}
+
+struct Rule {
+    predicate: Predicate,
+    transform: Transform,
+}
+
+struct Rules(Vec<Rule>);
+
+impl Rule {
+    fn new(
+        predicate: impl Fn(Stone) -> bool + 'static,
+        transform: impl Fn(Stone) -> Option<Vec<Stone>> + 'static,
+    ) -> Self {
+        Self {
+            predicate: Box::new(predicate),
+            transform: Box::new(transform),
+        }
+    }
+}
+
+// Parses a single `predicate -> transform` line, where the predicate is a
+// number, `even-digits`, `odd-digits` or `*`, and the transform is a number,
+// `split`, `mul N` or `add N`.
+impl From<&str> for Rule {
+    fn from(txt: &str) -> Self {
+        let (predicate, transform) = txt.split_once("->").unwrap();
+        let predicate: Predicate = match predicate.trim() {
+            "*" => Box::new(|_| true),
+            "even-digits" => Box::new(|n| num_digits(n).is_multiple_of(2)),
+            "odd-digits" => Box::new(|n| !num_digits(n).is_multiple_of(2)),
+            value => {
+                let value: Stone = value.parse().unwrap();
+                Box::new(move |n| n == value)
+            }
+        };
+        let transform: Transform = match transform.split_whitespace().collect::<Vec<_>>()[..] {
+            ["split"] => Box::new(|n| Some(split(n))),
+            ["mul", factor] => {
+                let factor: Stone = factor.parse().unwrap();
+                Box::new(move |n| n.checked_mul(factor).map(|n| vec![n]))
+            }
+            ["add", term] => {
+                let term: Stone = term.parse().unwrap();
+                Box::new(move |n| n.checked_add(term).map(|n| vec![n]))
+            }
+            [value] => {
+                let value: Stone = value.parse().unwrap();
+                Box::new(move |_| Some(vec![value]))
+            }
+            _ => panic!("Unknown transform: {}", transform),
+        };
+        Self {
+            predicate,
+            transform,
+        }
+    }
+}
+
+// One rule per line, with blank lines and # comments ignored.
+impl From<&str> for Rules {
+    fn from(txt: &str) -> Self {
+        Rules(
+            txt.lines()
+                .map(|line| line.split('#').next().unwrap().trim())
+                .filter(|line| !line.is_empty())
+                .map(Rule::from)
+                .collect(),
+        )
     }
-    let num_digits = num.ilog10() + 1;
-    if num_digits % 2 == 0 {
-        let half = num_digits / 2;
-        vec![num / 10u64.pow(half), num % 10u64.pow(half)]
-    } else {
-        vec![num * 2024]
+}
+
+impl Rules {
+    fn puzzle() -> Self {
+        Rules(vec![
+            Rule::new(|n| n == 0, |_| Some(vec![1])),
+            Rule::new(|n| num_digits(n).is_multiple_of(2), |n| Some(split(n))),
+            Rule::new(|_| true, |n| n.checked_mul(2024).map(|n| vec![n])),
+        ])
+    }
+
+    // The first matching rule wins. A stone matching no rule is left as is.
+    fn blink(&self, stone: Stone, blink: u32) -> Result<Vec<Stone>, Overflow> {
+        match self.0.iter().find(|rule| (rule.predicate)(stone)) {
+            Some(rule) => (rule.transform)(stone).ok_or(Overflow::Stone { blink, stone }),
+            None => Ok(vec![stone]),
+        }
     }
 }
 
-fn solve(stones: &Vec<Stone>, num_blinks: u32) -> u64 {
-    let mut counter: HashMap<Stone, u64> = HashMap::new();
+fn num_digits(num: Stone) -> u32 {
+    num.checked_ilog10().unwrap_or(0) + 1
+}
+
+fn split(num: Stone) -> Vec<Stone> {
+    let half = num_digits(num) / 2;
+    vec![num / (10 as Stone).pow(half), num % (10 as Stone).pow(half)]
+}
+
+#[derive(Debug)]
+struct Generation {
+    blink: u32,
+    total: Count,
+    distinct: usize,
+    largest: Stone,
+    // Distinct values seen in this or any earlier generation.
+    reachable: usize,
+}
+
+fn count_stones(stones: &[Stone]) -> HashMap<Stone, Count> {
+    let mut counter: HashMap<Stone, Count> = HashMap::new();
 
     stones.iter().for_each(|stone| {
         *counter.entry(*stone).or_insert(0) += 1;
     });
+    counter
+}
 
-    // Store the unique values with their counts in a hashmap
-    // Calculate the next generation:
-    // Each new stone carries over the count from the previous generation.
-    // Also, this new generation contains duplicate stones: their counts are added together.
+fn total(counter: &HashMap<Stone, Count>, blink: u32) -> Result<Count, Overflow> {
+    counter
+        .values()
+        .try_fold(0 as Count, |sum, count| sum.checked_add(*count))
+        .ok_or(Overflow::Count { blink })
+}
 
-    for _ in 0..num_blinks {
-        let generation: Vec<(Stone, u64)> = counter
-            .keys()
-            .flat_map(|stone| {
-                blink(*stone)
-                    .iter()
-                    .map(|new_stone| (*new_stone, *counter.get(stone).unwrap_or(&1)))
-                    .collect::<Vec<(Stone, u64)>>()
-            })
-            .collect();
+// Store the unique values with their counts in a hashmap
+// Calculate the next generation:
+// Each new stone carries over the count from the previous generation.
+// Also, this new generation contains duplicate stones: their counts are added together.
+fn evolve(
+    counter: &HashMap<Stone, Count>,
+    blink: u32,
+    rules: &Rules,
+) -> Result<HashMap<Stone, Count>, Overflow> {
+    let mut summer: HashMap<Stone, Count> = HashMap::new();
+    for (stone, count) in counter.iter() {
+        for new_stone in rules.blink(*stone, blink)? {
+            let total = summer.entry(new_stone).or_insert(0);
+            *total = total.checked_add(*count).ok_or(Overflow::Count { blink })?;
+        }
+    }
+    Ok(summer)
+}
+
+// Blinks are numbered from 1 when reporting an overflow.
+fn solve(stones: &[Stone], num_blinks: u32, rules: &Rules) -> Result<Count, Overflow> {
+    let mut counter = count_stones(stones);
+    for blink in 1..=num_blinks {
+        counter = evolve(&counter, blink, rules)?;
+    }
+    total(&counter, num_blinks)
+}
 
-        let mut summer: HashMap<Stone, u64> = HashMap::new();
-        for (stone, count) in generation {
-            *summer.entry(stone).or_insert(0) += count
+// Reports every generation from the initial stones (blink 0) onwards.
+fn statistics(
+    stones: &[Stone],
+    num_blinks: u32,
+    rules: &Rules,
+) -> Result<Vec<Generation>, Overflow> {
+    let mut counter = count_stones(stones);
+    let mut reachable: HashSet<Stone> = HashSet::new();
+    let mut generations = Vec::new();
+    for blink in 0..=num_blinks {
+        if blink > 0 {
+            counter = evolve(&counter, blink, rules)?;
         }
-        counter = summer;
+        reachable.extend(counter.keys());
+        generations.push(Generation {
+            blink,
+            total: total(&counter, blink)?,
+            distinct: counter.len(),
+            largest: counter.keys().copied().max().unwrap_or(0),
+            reachable: reachable.len(),
+        });
     }
-    counter.values().sum()
+    Ok(generations)
+}
+
+// Once a blink produces no value that hasn't been seen before, every later
+// blink only maps seen values onto seen values, so the reachable set is closed.
+fn closure(generations: &[Generation]) -> Option<u32> {
+    generations
+        .windows(2)
+        .find(|pair| pair[0].reachable == pair[1].reachable)
+        .map(|pair| pair[1].blink)
+}
+
+fn to_csv(generations: &[Generation]) -> String {
+    let mut csv = String::from("blink,total,distinct,largest,reachable\n");
+    for g in generations {
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            g.blink, g.total, g.distinct, g.largest, g.reachable
+        );
+    }
+    csv
+}
+
+// Removes `option` and the value following it from args.
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.remove(i);
+    Some(args.remove(i))
 }
 
 fn main() {
     let stones: Vec<Stone> = INPUT
         .split_whitespace()
-        .map(|s| s.parse::<u64>().unwrap())
+        .map(|s| s.parse::<Stone>().unwrap())
         .collect();
 
-    println!("Part 1: {}", solve(&stones, 25));
-    println!("Part 2: {}", solve(&stones, 75));
+    // cargo run -- [--rules <file>] [<blinks> | stats <blinks> > stats.csv]
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let rules = match take_option(&mut args, "--rules") {
+        Some(path) => Rules::from(std::fs::read_to_string(path).unwrap().as_str()),
+        None => Rules::puzzle(),
+    };
+
+    if args.len() == 2 && args[0] == "stats" {
+        let generations = match statistics(&stones, args[1].parse().unwrap(), &rules) {
+            Ok(generations) => generations,
+            Err(overflow) => {
+                eprintln!("{}", overflow);
+                std::process::exit(1);
+            }
+        };
+        print!("{}", to_csv(&generations));
+        match closure(&generations) {
+            Some(blink) => eprintln!(
+                "Reachable values closed at blink {}: {} values",
+                blink, generations[blink as usize].reachable
+            ),
+            None => eprintln!("Reachable values still growing"),
+        }
+        return;
+    }
+
+    // Rules read from a file may overflow where the puzzle's don't.
+    let show = |result: Result<Count, Overflow>| match result {
+        Ok(count) => count.to_string(),
+        Err(overflow) => overflow.to_string(),
+    };
+    println!("Part 1: {}", show(solve(&stones, 25, &rules)));
+    println!("Part 2: {}", show(solve(&stones, 75, &rules)));
+
+    if let Some(num_blinks) = args.first() {
+        let count = solve(&stones, num_blinks.parse().unwrap(), &rules);
+        println!("{} blinks: {}", num_blinks, show(count));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The puzzle's own rules, written in the rule config format.
+    const PUZZLE_RULES: &str = "
+    # predicate -> transform
+    0 -> 1
+    even-digits -> split
+    * -> mul 2024
+    ";
+
+    // Blinks the full row of stones one by one. Only practical for a few blinks,
+    // but useful for checking `solve` against a new set of rules.
+    fn simulate(stones: &[Stone], num_blinks: u32, rules: &Rules) -> Result<Count, Overflow> {
+        let mut stones = stones.to_vec();
+        for blink in 1..=num_blinks {
+            let mut next = Vec::new();
+            for stone in stones {
+                next.extend(rules.blink(stone, blink)?);
+            }
+            stones = next;
+        }
+        Ok(stones.len() as Count)
+    }
+
+    fn stones() -> Vec<Stone> {
+        INPUT
+            .split_whitespace()
+            .map(|s| s.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn example() {
+        let rules = Rules::puzzle();
+        assert_eq!(solve(&[125, 17], 6, &rules).unwrap(), 22);
+        assert_eq!(solve(&[125, 17], 25, &rules).unwrap(), 55312);
+    }
+
+    #[test]
+    fn configured_rules_match_built_in_rules() {
+        let stones = stones();
+        let configured = Rules::from(PUZZLE_RULES);
+        for blinks in [25, 75] {
+            assert_eq!(
+                solve(&stones, blinks, &configured).unwrap(),
+                solve(&stones, blinks, &Rules::puzzle()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn solve_matches_simulation() {
+        let stones = stones();
+        let configured = Rules::from(PUZZLE_RULES);
+        assert_eq!(
+            solve(&stones, 25, &configured).unwrap(),
+            simulate(&stones, 25, &configured).unwrap()
+        );
+    }
+
+    #[test]
+    fn huge_multiplier_overflows_on_the_first_blink() {
+        let rules = Rules::from(format!("* -> mul {}", Stone::MAX).as_str());
+        assert!(matches!(
+            solve(&[125, 17], 25, &rules),
+            Err(Overflow::Stone { blink: 1, .. })
+        ));
+        assert!(matches!(
+            simulate(&[125, 17], 25, &rules),
+            Err(Overflow::Stone { blink: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn example_statistics() {
+        let generations = statistics(&[125, 17], 20, &Rules::puzzle()).unwrap();
+        let totals: Vec<Count> = generations.iter().take(7).map(|g| g.total).collect();
+        assert_eq!(totals, [2, 3, 4, 5, 9, 13, 22]);
+        assert_eq!(closure(&generations), Some(15));
+        assert_eq!(generations[15].reachable, 76);
+        assert_eq!(closure(&generations[..15]), None);
+
+        let csv = to_csv(&generations[..3]);
+        assert_eq!(
+            csv,
+            "blink,total,distinct,largest,reachable\n\
+             0,2,2,125,2\n\
+             1,3,3,253000,5\n\
+             2,4,4,14168,9\n"
+        );
+    }
+
+    #[test]
+    fn statistics_report_overflow() {
+        let rules = Rules::from(format!("* -> mul {}", Stone::MAX).as_str());
+        assert!(matches!(
+            statistics(&[125, 17], 5, &rules),
+            Err(Overflow::Stone { blink: 1, .. })
+        ));
+    }
 }
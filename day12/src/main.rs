@@ -1,7 +1,5 @@
 mod point;
 
-use std::collections::HashSet;
-
 use point::Point;
 
 type Direction = (i32, i32);
@@ -13,38 +11,54 @@ const RIGHT: Direction = (0, 1);
 
 const DIRECTIONS: [Direction; 4] = [UP, DOWN, LEFT, RIGHT];
 
+const CORNERS: [(Direction, Direction); 4] = [(UP, LEFT), (UP, RIGHT), (DOWN, LEFT), (DOWN, RIGHT)];
+
 #[derive(Debug)]
 struct Plot {
-    points: HashSet<Point>,
+    label: usize,
+    plant: char,
+    area: u32,
     perimeter: u32,
+    sides: u32,
+    top_left: Point,
+    bottom_right: Point,
 }
 
 #[derive(Debug)]
 struct Garden {
     garden: Vec<Vec<char>>,
-    uncharted: HashSet<Point>,
     width: i32,
     height: i32,
 }
 
+impl std::fmt::Display for Plot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} '{}' area {} perimeter {} sides {} within {}-{}",
+            self.label,
+            self.plant,
+            self.area,
+            self.perimeter,
+            self.sides,
+            self.top_left,
+            self.bottom_right
+        )
+    }
+}
+
 impl Garden {
     fn new(garden: Vec<Vec<char>>) -> Self {
         let width = garden[0].len() as i32;
         let height = garden.len() as i32;
-        let uncharted = (0..width)
-            .flat_map(|row| (0..height).map(move |col| Point::new(row, col)))
-            .collect();
+        assert!(
+            garden.iter().all(|row| row.len() == width as usize),
+            "Garden rows must all be the same length"
+        );
         Self {
             width,
             height,
             garden,
-            uncharted,
-        }
-    }
-
-    fn mark_charted(&mut self, plot: &Plot) {
-        for point in plot.points.iter() {
-            self.uncharted.remove(point);
         }
     }
 
@@ -65,49 +79,229 @@ impl Garden {
             .filter(|p| self.is_valid(*p) && self.at(*p) == label)
             .collect()
     }
-    fn build_plot(&self, initial: Point) -> Plot {
-        let mut queue = vec![initial];
-        let mut found = vec![initial];
-        while let Some(point) = queue.pop() {
-            let neighbours = self.find_neighbours(point);
-            neighbours.iter().for_each(|p| {
-                if !found.contains(p) {
-                    found.push(*p);
-                    queue.push(*p);
+
+    // Labels every region in a single scan, returning each point's label
+    // alongside the plots. Each unlabelled point seeds a flood fill, so every
+    // point is only queued once.
+    fn label(&self) -> (Vec<Vec<usize>>, Vec<Plot>) {
+        let mut labels = vec![vec![None; self.width as usize]; self.height as usize];
+        let mut plots = Vec::new();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if labels[row as usize][col as usize].is_some() {
+                    continue;
                 }
-            });
+                let label = plots.len();
+                labels[row as usize][col as usize] = Some(label);
+                let initial = Point::new(row, col);
+                let mut queue = vec![initial];
+                let mut found = vec![initial];
+                while let Some(point) = queue.pop() {
+                    for p in self.find_neighbours(point) {
+                        if labels[p.row as usize][p.col as usize].is_none() {
+                            labels[p.row as usize][p.col as usize] = Some(label);
+                            found.push(p);
+                            queue.push(p);
+                        }
+                    }
+                }
+                plots.push(self.build_plot(label, &found));
+            }
+        }
+        let labels = labels
+            .into_iter()
+            .map(|row| row.into_iter().map(Option::unwrap).collect())
+            .collect();
+        (labels, plots)
+    }
+
+    fn plots(&self) -> Vec<Plot> {
+        self.label().1
+    }
+
+    // Draws the garden with each region on its own background colour. Hues
+    // step by the golden ratio so neighbouring labels are easy to tell apart.
+    fn render(&self) -> String {
+        let (labels, _) = self.label();
+        let mut output = String::new();
+        for (row, plants) in self.garden.iter().enumerate() {
+            for (col, plant) in plants.iter().enumerate() {
+                let hue = (labels[row][col] as f64 * 0.618_033_988_75).fract();
+                let (r, g, b) = hsv_to_rgb(hue, 0.55, 0.95);
+                output += &format!("\x1b[48;2;{r};{g};{b}m\x1b[30m{plant}");
+            }
+            output += "\x1b[0m\n";
         }
-        let perimeter = self.calculate_perimeter(&found);
+        output
+    }
+
+    fn build_plot(&self, label: usize, points: &[Point]) -> Plot {
+        let top_left = points.iter().fold(points[0], |corner, p| {
+            Point::new(corner.row.min(p.row), corner.col.min(p.col))
+        });
+        let bottom_right = points.iter().fold(points[0], |corner, p| {
+            Point::new(corner.row.max(p.row), corner.col.max(p.col))
+        });
         Plot {
-            points: HashSet::from_iter(found),
-            perimeter,
+            label,
+            plant: self.at(points[0]),
+            area: points.len() as u32,
+            perimeter: self.calculate_perimeter(points),
+            sides: self.calculate_sides(points),
+            top_left,
+            bottom_right,
         }
     }
 
     fn calculate_perimeter(&self, points: &[Point]) -> u32 {
-        points.iter().map(|p| {
-            let neighbours = self.find_neighbours(*p);
-            4 - neighbours.len() as u32
-        }).sum()
+        points
+            .iter()
+            .map(|p| {
+                let neighbours = self.find_neighbours(*p);
+                4 - neighbours.len() as u32
+            })
+            .sum()
+    }
+
+    // A polygon has as many sides as corners. Each point contributes an outer
+    // corner where both neighbours in a corner's directions are outside the
+    // plot, and an inner corner where both are inside but the diagonal isn't.
+    fn calculate_sides(&self, points: &[Point]) -> u32 {
+        let label = self.at(points[0]);
+        let inside = |p: Point| self.is_valid(p) && self.at(p) == label;
+        points
+            .iter()
+            .map(|&p| {
+                CORNERS
+                    .iter()
+                    .filter(|((row1, col1), (row2, col2))| {
+                        let first = inside(p + Point::new(*row1, *col1));
+                        let second = inside(p + Point::new(*row2, *col2));
+                        let diagonal = inside(p + Point::new(row1 + row2, col1 + col2));
+                        (!first && !second) || (first && second && !diagonal)
+                    })
+                    .count() as u32
+            })
+            .sum()
     }
 }
 
-fn solve_part1(grid: &[Vec<char>]) -> u32 {
-    let mut garden = Garden::new(grid.to_vec());
-    let mut sum = 0;
-    while let Some(point) = garden.uncharted.iter().next() {
-        let plot = garden.build_plot(*point);
-        garden.mark_charted(&plot);
-        sum += plot.points.len() as u32 * plot.perimeter
+impl From<&str> for Garden {
+    fn from(txt: &str) -> Self {
+        Garden::new(txt.lines().map(|line| line.chars().collect()).collect())
     }
-    sum
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> (u8, u8, u8) {
+    let sector = hue * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let scale = |c: f64| ((c + m) * 255.0).round() as u8;
+    (scale(r), scale(g), scale(b))
+}
+
+fn solve_part1(plots: &[Plot]) -> u32 {
+    plots.iter().map(|plot| plot.area * plot.perimeter).sum()
+}
+
+fn solve_part2(plots: &[Plot]) -> u32 {
+    plots.iter().map(|plot| plot.area * plot.sides).sum()
+}
+
+// Removes `option` and the value following it from args.
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.remove(i);
+    Some(args.remove(i))
 }
 
 fn main() {
-    let grid: Vec<Vec<char>> = include_str!("puzzle.txt")
-        .lines()
-        .map(|line| line.chars().collect())
-        .collect();
+    // cargo run -- [--input <name>] [render]
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = match take_option(&mut args, "--input").as_deref() {
+        None | Some("puzzle") => include_str!("puzzle.txt"),
+        Some("example") => include_str!("example.txt"),
+        Some("small") => include_str!("small.txt"),
+        Some("enclosed") => include_str!("enclosed.txt"),
+        Some("e-shape") => include_str!("e_shape.txt"),
+        Some("inner") => include_str!("inner.txt"),
+        Some("wide") => include_str!("wide.txt"),
+        Some(name) => panic!("Unknown input: {}", name),
+    };
+    let garden = Garden::from(input);
+    let plots = garden.plots();
+
+    if args.first().map(String::as_str) == Some("render") {
+        print!("{}", garden.render());
+    }
+
+    println!("Part 1: {}", solve_part1(&plots));
+    println!("Part 2: {}", solve_part2(&plots));
+    if let Some(largest) = plots.iter().max_by_key(|plot| plot.area) {
+        println!("Largest plot: {}", largest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(input: &str) -> (u32, u32) {
+        let plots = Garden::from(input).plots();
+        (solve_part1(&plots), solve_part2(&plots))
+    }
 
-    println!("Part 1: {}", solve_part1(&grid));
+    #[test]
+    fn example() {
+        assert_eq!(prices(include_str!("example.txt")), (1930, 1206));
+    }
+
+    #[test]
+    fn small() {
+        assert_eq!(prices(include_str!("small.txt")), (140, 80));
+    }
+
+    // Single X plots sitting inside the O region.
+    #[test]
+    fn enclosed() {
+        assert_eq!(prices(include_str!("enclosed.txt")), (772, 436));
+    }
+
+    #[test]
+    fn e_shape() {
+        assert_eq!(prices(include_str!("e_shape.txt")), (692, 236));
+    }
+
+    // Two B regions inside the A region, touching it only diagonally where
+    // they meet, so the A region's inner corners are what count.
+    #[test]
+    fn inner_regions() {
+        assert_eq!(prices(include_str!("inner.txt")), (1184, 368));
+    }
+
+    // 7 columns by 3 rows, with C enclosed by A.
+    #[test]
+    fn wide() {
+        assert_eq!(prices(include_str!("wide.txt")), (308, 138));
+    }
+
+    #[test]
+    fn wide_labels() {
+        let (labels, plots) = Garden::from(include_str!("wide.txt")).label();
+        assert_eq!(plots.len(), 4);
+        assert_eq!(labels.len(), 3);
+        assert!(labels.iter().all(|row| row.len() == 7));
+        assert_eq!(labels[1][1], labels[1][2]);
+        assert_ne!(labels[1][1], labels[0][0]);
+        assert_eq!(labels[2][6], labels[0][4]);
+    }
 }
@@ -1,49 +1,388 @@
-use regex::Regex;
-extern crate nalgebra as na;
-use na::{Matrix2, Vector2};
-
-fn solve_matrix(a: Matrix2<f64>, b: Vector2<f64>) -> Option<Vector2<f64>> {
-    let decomp = a.lu();
-    decomp.solve(&b)
-}
-
-fn approx(val: f64) -> bool {
-    (val - val.round()).abs() * 100.0 < 1.0
-}
-
-fn solve(data : &Vec<&str>, offset : u64) -> u64 {
-    let re = Regex::new(r"X\+(?<x1>\d+), Y\+(?<y1>\d+).* X\+(?<x2>\d+), Y\+(?<y2>\d+).* X=(?<X>\d+), Y=(?<Y>\d+)$").unwrap();
-
-    let mut sum = 0;
-    for entry in data {
-        let entry =entry.replace("\n", " ");
-        let caps = re.captures(&entry).unwrap();
-        let x1 = caps["x1"].parse::<u64>().unwrap();
-        let y1 = caps["y1"].parse::<u64>().unwrap();
-        let x2 = caps["x2"].parse::<u64>().unwrap();
-        let y2 = caps["y2"].parse::<u64>().unwrap();
-        let x = caps["X"].parse::<u64>().unwrap() + offset;
-        let y = caps["Y"].parse::<u64>().unwrap() + offset;
-        let a1: Matrix2<f64> = Matrix2::new(x1 as f64, x2 as f64, y1 as f64, y2 as f64);
-        let b = Vector2::new(x as f64, y as f64);
-        sum += match solve_matrix(a1, b) {
-            Some(x) => {
-                if approx(x[0]) && approx(x[1]) {
-                    x[0].round() as u64 * 3 + x[1].round() as u64
-                } else {
-                    0
+mod parse;
+
+use parse::parse_machines;
+use std::fmt;
+
+type Vector = (i128, i128);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Button {
+    label: char,
+    movement: Vector,
+    cost: i128,
+    limit: Option<i128>,
+}
+
+#[derive(Debug, PartialEq)]
+struct ClawMachine {
+    buttons: Vec<Button>,
+    prize: Vector,
+}
+
+impl Button {
+    fn allows(&self, presses: i128) -> bool {
+        presses >= 0 && self.limit.is_none_or(|limit| presses <= limit)
+    }
+    // The most presses worth trying: the press limit, or the point where the
+    // button overshoots the prize along an axis no button moves back on.
+    fn max_presses(&self, prize: Vector, buttons: &[Button]) -> Option<i128> {
+        let forward = |axis: fn(Vector) -> i128| buttons.iter().all(|b| axis(b.movement) >= 0);
+        let (x, y) = self.movement;
+        let overshoot = [
+            (x, prize.0, forward(|v| v.0)),
+            (y, prize.1, forward(|v| v.1)),
+        ]
+        .into_iter()
+        .filter(|&(step, _, forward)| step > 0 && forward)
+        .map(|(step, target, _)| target / step)
+        .min();
+        match (overshoot, self.limit) {
+            (Some(overshoot), Some(limit)) => Some(overshoot.min(limit)),
+            (overshoot, limit) => overshoot.or(limit),
+        }
+    }
+}
+
+// Most combinations of extra button presses the search will try.
+const MAX_COMBINATIONS: u128 = 100_000_000;
+
+// Why the cheapest presses could not be searched for. Buttons are numbered
+// from 1 in the order they are listed.
+#[derive(Debug, PartialEq)]
+enum SearchError {
+    Unbounded { button: usize },
+    TooLarge,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::Unbounded { button } => write!(
+                f,
+                "button {} has no press limit and nothing stops it overshooting the prize",
+                button
+            ),
+            SearchError::TooLarge => write!(
+                f,
+                "more than {} combinations of button presses to try",
+                MAX_COMBINATIONS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl ClawMachine {
+    // The fewest tokens needed to win the prize, if it can be won at all.
+    fn cheapest(&self) -> Result<Option<u64>, SearchError> {
+        Ok(cheapest(&self.buttons, self.prize)?.map(|tokens| tokens as u64))
+    }
+}
+
+fn cross((x1, y1): Vector, (x2, y2): Vector) -> i128 {
+    x1 * y2 - y1 * x2
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -(-a).div_euclid(b)
+}
+
+// Returns (g, x, y) where a*x + b*y = g = gcd(a, b).
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+// Cheapest (na, nb) with na*u + nb*v = w that satisfies both press ranges
+// where the line has more than one solution. The caller checks the result.
+fn cheapest_on_line(u: i128, v: i128, w: i128, a: &Button, b: &Button) -> Option<(i128, i128)> {
+    match (u, v) {
+        (0, 0) => (w == 0).then_some((0, 0)),
+        (0, _) => (w % v == 0).then_some((0, w / v)),
+        (_, 0) => (w % u == 0).then_some((w / u, 0)),
+        _ => {
+            let (g, x, y) = match extended_gcd(u, v) {
+                (g, x, y) if g < 0 => (-g, -x, -y),
+                gxy => gxy,
+            };
+            if w % g != 0 {
+                return None;
+            }
+            // Every solution is (na0 + k*step_a, nb0 + k*step_b) for integer k.
+            // Each constraint c + k*s >= 0 bounds k on one side, and the cost is
+            // linear in k so the cheapest is at an end of the allowed range.
+            let (na0, nb0) = (x * (w / g), y * (w / g));
+            let (step_a, step_b) = (v / g, -u / g);
+            let mut constraints = vec![(na0, step_a), (nb0, step_b)];
+            if let Some(limit) = a.limit {
+                constraints.push((limit - na0, -step_a));
+            }
+            if let Some(limit) = b.limit {
+                constraints.push((limit - nb0, -step_b));
+            }
+            let lowest = constraints
+                .iter()
+                .filter(|&&(_, s)| s > 0)
+                .map(|&(c, s)| ceil_div(-c, s))
+                .max();
+            let highest = constraints
+                .iter()
+                .filter(|&&(_, s)| s < 0)
+                .map(|&(c, s)| c.div_euclid(-s))
+                .min();
+            if let (Some(lowest), Some(highest)) = (lowest, highest) {
+                if lowest > highest {
+                    return None;
                 }
             }
-            None => 0,
+            lowest
+                .into_iter()
+                .chain(highest)
+                .map(|k| (na0 + k * step_a, nb0 + k * step_b))
+                .min_by_key(|&(na, nb)| na * a.cost + nb * b.cost)
         }
     }
-    sum
+}
+
+// Solves na*a + nb*b = prize exactly using Cramer's rule, returning the
+// button presses only when they are allowed integers. When the buttons
+// are collinear there may be many solutions, so pick the cheapest.
+fn solve_presses(a: &Button, b: &Button, prize: Vector) -> Option<(u64, u64)> {
+    let det = cross(a.movement, b.movement);
+    let (na, nb) = if det != 0 {
+        let (na, nb) = (cross(prize, b.movement), cross(a.movement, prize));
+        if na % det != 0 || nb % det != 0 {
+            return None;
+        }
+        (na / det, nb / det)
+    } else {
+        let direction = if a.movement != (0, 0) {
+            a.movement
+        } else {
+            b.movement
+        };
+        if direction == (0, 0) {
+            (prize == (0, 0)).then_some((0, 0))?
+        } else if cross(direction, prize) != 0 {
+            return None;
+        } else if direction.0 != 0 {
+            cheapest_on_line(a.movement.0, b.movement.0, prize.0, a, b)?
+        } else {
+            cheapest_on_line(a.movement.1, b.movement.1, prize.1, a, b)?
+        }
+    };
+    (a.allows(na) && b.allows(nb)).then_some((na as u64, nb as u64))
+}
+
+// A small integer program: minimise the total cost of presses that land
+// exactly on the prize. The last two buttons are solved directly, and the
+// others are enumerated up to their press limit (or until they overshoot
+// the prize) with the pair solved for each choice. Buttons that could be
+// pressed any number of times make this an error rather than a guess.
+fn cheapest(buttons: &[Button], prize: Vector) -> Result<Option<i128>, SearchError> {
+    let (extra, pair) = buttons.split_at(buttons.len().saturating_sub(2));
+    let bounds = extra
+        .iter()
+        .enumerate()
+        .map(|(i, button)| {
+            button
+                .max_presses(prize, buttons)
+                .ok_or(SearchError::Unbounded { button: i + 1 })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    bounds
+        .iter()
+        .try_fold(1u128, |total, &bound| {
+            total.checked_mul(bound.max(0) as u128 + 1)
+        })
+        .filter(|&total| total <= MAX_COMBINATIONS)
+        .ok_or(SearchError::TooLarge)?;
+    Ok(search(extra, &bounds, pair, prize))
+}
+
+fn search(extra: &[Button], bounds: &[i128], pair: &[Button], prize: Vector) -> Option<i128> {
+    let [first, rest @ ..] = extra else {
+        return match pair {
+            [] => (prize == (0, 0)).then_some(0),
+            [a] => {
+                let unused = Button {
+                    label: ' ',
+                    movement: (0, 0),
+                    cost: 0,
+                    limit: Some(0),
+                };
+                solve_presses(a, &unused, prize).map(|(na, _)| na as i128 * a.cost)
+            }
+            [a, b, ..] => {
+                solve_presses(a, b, prize).map(|(na, nb)| na as i128 * a.cost + nb as i128 * b.cost)
+            }
+        };
+    };
+    (0..=bounds[0])
+        .filter_map(|presses| {
+            let (x, y) = first.movement;
+            let remaining = (prize.0 - presses * x, prize.1 - presses * y);
+            search(rest, &bounds[1..], pair, remaining).map(|tokens| tokens + presses * first.cost)
+        })
+        .min()
+}
+
+fn solve(machines: &[ClawMachine], offset: i128, limit: Option<i128>) -> Result<u64, SearchError> {
+    machines.iter().try_fold(0, |total, machine| {
+        let buttons = machine
+            .buttons
+            .iter()
+            .map(|button| Button { limit, ..*button })
+            .collect();
+        let prize = (machine.prize.0 + offset, machine.prize.1 + offset);
+        Ok(total + ClawMachine { buttons, prize }.cheapest()?.unwrap_or(0))
+    })
 }
 
 fn main() {
+    let machines = parse_machines(include_str!("puzzle.txt")).unwrap_or_else(|e| panic!("{}", e));
 
-    let data: Vec<&str> = include_str!("puzzle.txt").split("\n\n").collect();
+    for (part, offset, limit) in [(1, 0, Some(100)), (2, 10000000000000, None)] {
+        match solve(&machines, offset, limit) {
+            Ok(tokens) => println!("Part {}: {}", part, tokens),
+            Err(e) => eprintln!("Part {}: {}", part, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::ParseError;
+
+    fn button(movement: Vector, cost: i128, limit: Option<i128>) -> Button {
+        Button {
+            label: 'A',
+            movement,
+            cost,
+            limit,
+        }
+    }
+
+    #[test]
+    fn example_answers() {
+        let machines = parse_machines(include_str!("example.txt")).unwrap();
+        assert_eq!(solve(&machines, 0, Some(100)), Ok(480));
+        assert_eq!(solve(&machines, 10000000000000, None), Ok(875318608908));
+    }
+
+    #[test]
+    fn collinear_buttons_take_the_cheapest_point_on_the_line() {
+        // B is cheaper per step but capped at 4 presses, so A makes up the rest.
+        let a = button((2, 2), 3, None);
+        let b = button((1, 1), 1, Some(4));
+        assert_eq!(solve_presses(&a, &b, (10, 10)), Some((3, 4)));
+        assert_eq!(cheapest(&[a, b], (10, 10)), Ok(Some(13)));
+    }
 
-    println!("Part 1: {}", solve(&data, 0));
-    println!("Part 2: {}", solve(&data, 10000000000000));
+    #[test]
+    fn collinear_buttons_without_an_integer_solution() {
+        let a = button((2, 2), 3, None);
+        let b = button((4, 4), 1, None);
+        assert_eq!(solve_presses(&a, &b, (3, 3)), None);
+    }
+
+    #[test]
+    fn three_buttons() {
+        let buttons = [
+            button((1, 0), 1, None),
+            button((0, 1), 1, None),
+            button((1, 1), 1, None),
+        ];
+        assert_eq!(cheapest(&buttons, (3, 5)), Ok(Some(5)));
+        assert_eq!(cheapest(&buttons, (-1, 5)), Ok(None));
+    }
+
+    #[test]
+    fn negative_buttons_need_a_cap() {
+        // A once then B and C twice each costs 5, but without a cap A could be
+        // pressed any number of times and B and C could pull it back.
+        let uncapped = [
+            button((5, 5), 1, None),
+            button((-1, 0), 1, None),
+            button((0, -1), 1, None),
+        ];
+        assert_eq!(
+            cheapest(&uncapped, (3, 3)),
+            Err(SearchError::Unbounded { button: 1 })
+        );
+        let capped = uncapped.map(|button| Button {
+            limit: Some(10),
+            ..button
+        });
+        assert_eq!(cheapest(&capped, (3, 3)), Ok(Some(5)));
+    }
+
+    #[test]
+    fn too_many_combinations() {
+        let buttons = [
+            button((1, 1), 1, None),
+            button((1, 0), 1, None),
+            button((0, 1), 1, None),
+        ];
+        let prize = (10000000000000, 10000000000000);
+        assert_eq!(cheapest(&buttons, prize), Err(SearchError::TooLarge));
+    }
+
+    #[test]
+    fn parse_tolerates_crlf_whitespace_and_trailing_blank_lines() {
+        let messy = "Button A:  X+94, Y+34\r\n  Button B : X + 22 ,Y+67\r\nPrize: X= 8400, Y=5400  \r\n\r\n\r\n";
+        let example = parse_machines(include_str!("example.txt")).unwrap();
+        assert_eq!(parse_machines(messy).unwrap()[..], example[..1]);
+    }
+
+    #[test]
+    fn parse_negative_offsets() {
+        let machines =
+            parse_machines("Button A: X-5, Y+3\nButton B: X+1, Y-2\nPrize: X=-7, Y=4").unwrap();
+        let movements: Vec<Vector> = machines[0].buttons.iter().map(|b| b.movement).collect();
+        assert_eq!(movements, [(-5, 3), (1, -2)]);
+        assert_eq!(machines[0].prize, (-7, 4));
+    }
+
+    #[test]
+    fn parse_errors_name_the_block_and_line() {
+        let input = "Button A: X+1, Y+1\nPrize: X=1, Y=1\n\nButton A: X+1, Y+1\nButton C X+2\nPrize: X=1, Y=1";
+        assert_eq!(
+            parse_machines(input).unwrap_err(),
+            ParseError::BadLine {
+                block: 2,
+                line: 2,
+                text: "Button C X+2".to_string()
+            }
+        );
+        let input = "Button A: X+1, Y+1\nButton B: X+2, Y+2\n";
+        assert_eq!(
+            parse_machines(input).unwrap_err(),
+            ParseError::MissingPrize { block: 1, line: 3 }
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let swapped = "Button B: X+1, Y+2\nButton A: X-3, Y+4\nPrize: X=5, Y=6";
+        let machines = parse_machines(swapped).unwrap();
+        assert_eq!(machines[0].to_string(), swapped);
+        assert_eq!(machines[0].buttons[0].cost, 1);
+        assert_eq!(machines[0].buttons[1].cost, 3);
+
+        let inputs = [include_str!("example.txt"), include_str!("puzzle.txt")];
+        for machine in inputs
+            .into_iter()
+            .flat_map(|input| parse_machines(input).unwrap())
+        {
+            let reparsed = parse_machines(&machine.to_string()).unwrap();
+            assert_eq!(reparsed, [machine]);
+        }
+    }
 }
@@ -0,0 +1,115 @@
+use crate::{Button, ClawMachine};
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+static BUTTON: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^Button\s+(?<label>[A-Z])\s*:\s*X\s*(?<x>[+-]\s*\d+)\s*,\s*Y\s*(?<y>[+-]\s*\d+)$")
+        .unwrap()
+});
+
+static PRIZE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^Prize\s*:\s*X\s*=\s*(?<x>-?\s*\d+)\s*,\s*Y\s*=\s*(?<y>-?\s*\d+)$").unwrap()
+});
+
+// Blocks and lines are numbered from 1, counting within the block.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    BadLine {
+        block: usize,
+        line: usize,
+        text: String,
+    },
+    MissingPrize {
+        block: usize,
+        line: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::BadLine { block, line, text } => {
+                write!(f, "block {}, line {}: cannot parse {:?}", block, line, text)
+            }
+            ParseError::MissingPrize { block, line } => {
+                write!(f, "block {}, line {}: expected a prize line", block, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn number(txt: &str) -> Option<i128> {
+    txt.replace(char::is_whitespace, "")
+        .trim_start_matches('+')
+        .parse()
+        .ok()
+}
+
+impl ClawMachine {
+    // Parses one block of button lines followed by a prize line. Button A
+    // costs 3 tokens and every other button costs 1, wherever it is listed.
+    pub fn parse(block: usize, lines: &[&str]) -> Result<ClawMachine, ParseError> {
+        let bad_line = |line: usize| ParseError::BadLine {
+            block,
+            line: line + 1,
+            text: lines[line].to_string(),
+        };
+        let mut buttons = Vec::new();
+        let mut prize = None;
+        for (line, text) in lines.iter().enumerate() {
+            if prize.is_some() {
+                return Err(bad_line(line));
+            }
+            if let Some(caps) = BUTTON.captures(text.trim()) {
+                let movement = (
+                    number(&caps["x"]).ok_or_else(|| bad_line(line))?,
+                    number(&caps["y"]).ok_or_else(|| bad_line(line))?,
+                );
+                let label = caps["label"].chars().next().unwrap();
+                buttons.push(Button {
+                    label,
+                    cost: if label == 'A' { 3 } else { 1 },
+                    movement,
+                    limit: None,
+                });
+            } else if let Some(caps) = PRIZE.captures(text.trim()) {
+                prize = Some((
+                    number(&caps["x"]).ok_or_else(|| bad_line(line))?,
+                    number(&caps["y"]).ok_or_else(|| bad_line(line))?,
+                ));
+            } else {
+                return Err(bad_line(line));
+            }
+        }
+        let prize = prize.ok_or(ParseError::MissingPrize {
+            block,
+            line: lines.len() + 1,
+        })?;
+        Ok(ClawMachine { buttons, prize })
+    }
+}
+
+// Splits the input into blank-line separated blocks, accepting CRLF line
+// endings, stray whitespace and any number of trailing blank lines.
+pub fn parse_machines(input: &str) -> Result<Vec<ClawMachine>, ParseError> {
+    let lines: Vec<&str> = input.lines().collect();
+    lines
+        .split(|line| line.trim().is_empty())
+        .filter(|block| !block.is_empty())
+        .enumerate()
+        .map(|(i, block)| ClawMachine::parse(i + 1, block))
+        .collect()
+}
+
+impl fmt::Display for ClawMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for button in &self.buttons {
+            let (x, y) = button.movement;
+            writeln!(f, "Button {}: X{:+}, Y{:+}", button.label, x, y)?;
+        }
+        write!(f, "Prize: X={}, Y={}", self.prize.0, self.prize.1)
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{gcd, Bathroom, Position, Robot};
+
+// A frame has to stand this many standard deviations away from the
+// typical frame before we believe it contains a picture.
+const MIN_CONFIDENCE: f64 = 3.0;
+
+#[derive(Debug)]
+pub struct Detection {
+    pub frame: u64,
+    pub confidence: f64,
+}
+
+pub trait Detector {
+    fn name(&self) -> &str;
+    fn detect(&self, bathroom: &Bathroom, robots: &[Robot]) -> Option<Detection>;
+}
+
+// Robots huddled together have a small spread in both axes.
+pub struct Variance;
+
+// Clustered robots leave most rows and columns empty, lowering the entropy
+// of the row and column histograms.
+pub struct Entropy;
+
+// A picture puts most robots in one quadrant, minimising the safety factor.
+pub struct SafetyFactor;
+
+// x positions repeat every `width` seconds and y positions every `height`
+// seconds, so find the tightest frame on each axis independently and combine
+// the two with the Chinese remainder theorem.
+pub struct ChineseRemainder;
+
+impl Detector for Variance {
+    fn name(&self) -> &str {
+        "Variance"
+    }
+    fn detect(&self, bathroom: &Bathroom, robots: &[Robot]) -> Option<Detection> {
+        most_unusual(bathroom.period(), |t| {
+            let positions = bathroom.positions_at(robots, t as i64);
+            variance(positions.iter().map(|p| p.x as f64))
+                + variance(positions.iter().map(|p| p.y as f64))
+        })
+    }
+}
+
+impl Detector for Entropy {
+    fn name(&self) -> &str {
+        "Entropy"
+    }
+    fn detect(&self, bathroom: &Bathroom, robots: &[Robot]) -> Option<Detection> {
+        most_unusual(bathroom.period(), |t| {
+            let positions = bathroom.positions_at(robots, t as i64);
+            let mut columns = vec![0; bathroom.width as usize];
+            let mut rows = vec![0; bathroom.height as usize];
+            for Position { x, y } in positions {
+                columns[x as usize] += 1;
+                rows[y as usize] += 1;
+            }
+            entropy(&columns) + entropy(&rows)
+        })
+    }
+}
+
+impl Detector for SafetyFactor {
+    fn name(&self) -> &str {
+        "Safety factor"
+    }
+    fn detect(&self, bathroom: &Bathroom, robots: &[Robot]) -> Option<Detection> {
+        most_unusual(bathroom.period(), |t| {
+            let positions = bathroom.positions_at(robots, t as i64);
+            bathroom.safety_factor((2, 2), &positions) as f64
+        })
+    }
+}
+
+impl Detector for ChineseRemainder {
+    fn name(&self) -> &str {
+        "Chinese remainder"
+    }
+    fn detect(&self, bathroom: &Bathroom, robots: &[Robot]) -> Option<Detection> {
+        let (width, height) = (bathroom.width as u64, bathroom.height as u64);
+        let x = most_unusual(width, |t| {
+            variance(
+                bathroom
+                    .positions_at(robots, t as i64)
+                    .iter()
+                    .map(|p| p.x as f64),
+            )
+        })?;
+        let y = most_unusual(height, |t| {
+            variance(
+                bathroom
+                    .positions_at(robots, t as i64)
+                    .iter()
+                    .map(|p| p.y as f64),
+            )
+        })?;
+        let frame = chinese_remainder((x.frame, width), (y.frame, height))?;
+        Some(Detection {
+            frame,
+            confidence: x.confidence.min(y.confidence),
+        })
+    }
+}
+
+// Scores every frame in 0..frames and returns the first frame with the lowest
+// score, provided it stands out far enough from the rest.
+fn most_unusual(frames: u64, score: impl Fn(u64) -> f64) -> Option<Detection> {
+    let scores: Vec<f64> = (0..frames).map(score).collect();
+    let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+    let spread = variance(scores.iter().copied()).sqrt();
+    let (frame, best) = scores
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if spread == 0.0 {
+        return None;
+    }
+    let confidence = (mean - best) / spread;
+    (confidence >= MIN_CONFIDENCE).then_some(Detection {
+        frame: frame as u64,
+        confidence,
+    })
+}
+
+fn variance(values: impl Iterator<Item = f64>) -> f64 {
+    let values: Vec<f64> = values.collect();
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+}
+
+fn entropy(counts: &[u32]) -> f64 {
+    let total: u32 = counts.iter().sum();
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// Finds t with t = a (mod m) and t = b (mod n), if one exists.
+fn chinese_remainder((a, m): (u64, u64), (b, n): (u64, u64)) -> Option<u64> {
+    (0..n / gcd(m, n)).map(|k| a + k * m).find(|t| t % n == b)
+}
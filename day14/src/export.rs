@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+// Background, then progressively brighter greens for 1, 2 and 3+ robots.
+const PALETTE: [[u8; 3]; 4] = [[0, 0, 0], [0, 110, 0], [0, 190, 0], [170, 255, 170]];
+
+// Each grid cell is drawn as a square of this many pixels.
+const SCALE: usize = 4;
+
+// Hundredths of a second between animation frames.
+const DELAY: u16 = 10;
+
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    Ppm,
+    Png,
+    Gif,
+}
+
+impl FromStr for Format {
+    type Err = io::Error;
+
+    fn from_str(txt: &str) -> io::Result<Self> {
+        match txt {
+            "ppm" => Ok(Format::Ppm),
+            "png" => Ok(Format::Png),
+            "gif" => Ok(Format::Gif),
+            _ => Err(invalid(format!("unknown image format {:?}", txt))),
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+// Parses a comma separated list of frames and inclusive ranges, such as
+// "0..=100,7520", keeping the order given.
+pub fn parse_frames(spec: &str) -> io::Result<Vec<RangeInclusive<i64>>> {
+    let number = |txt: &str| {
+        txt.trim()
+            .parse::<i64>()
+            .map_err(|_| invalid(format!("cannot parse frame {:?}", txt)))
+    };
+    spec.split(',')
+        .map(|part| {
+            let range = match part.split_once("..=") {
+                Some((start, end)) => number(start)?..=number(end)?,
+                None => number(part)?..=number(part)?,
+            };
+            if range.is_empty() {
+                return Err(invalid(format!(
+                    "start {} is after end {}",
+                    range.start(),
+                    range.end()
+                )));
+            }
+            Ok(range)
+        })
+        .collect()
+}
+
+// Palette index of every pixel in the scaled-up image, row by row.
+fn pixels(grid: &[Vec<u32>]) -> Vec<u8> {
+    grid.iter()
+        .flat_map(|row| {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|&count| std::iter::repeat_n(count.min(3) as u8, SCALE))
+                .collect();
+            std::iter::repeat_n(line, SCALE).flatten()
+        })
+        .collect()
+}
+
+fn rgb(grid: &[Vec<u32>]) -> Vec<u8> {
+    pixels(grid)
+        .iter()
+        .flat_map(|&index| PALETTE[index as usize])
+        .collect()
+}
+
+fn dimensions(grid: &[Vec<u32>]) -> (usize, usize) {
+    (grid[0].len() * SCALE, grid.len() * SCALE)
+}
+
+pub fn write_ppm(path: &Path, grid: &[Vec<u32>]) -> io::Result<()> {
+    let (width, height) = dimensions(grid);
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    file.write_all(&rgb(grid))
+}
+
+pub fn write_png(path: &Path, grid: &[Vec<u32>]) -> io::Result<()> {
+    let (width, height) = dimensions(grid);
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb(grid))?;
+    Ok(())
+}
+
+// Encodes frames as they arrive, taking the image size from the first.
+pub fn write_gif(path: &Path, frames: impl Iterator<Item = Vec<Vec<u32>>>) -> io::Result<()> {
+    let mut frames = frames.peekable();
+    let first = frames
+        .peek()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no frames to write"))?;
+    let (width, height) = dimensions(first);
+    let file = BufWriter::new(File::create(path)?);
+    let palette = PALETTE.concat();
+    let mut encoder =
+        gif::Encoder::new(file, width as u16, height as u16, &palette).map_err(io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(io::Error::other)?;
+    for grid in frames {
+        let mut frame =
+            gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels(&grid), None);
+        frame.delay = DELAY;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
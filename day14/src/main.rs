@@ -1,26 +1,17 @@
+mod detector;
+mod export;
 mod point;
 
+use detector::{ChineseRemainder, Detector, Entropy, SafetyFactor, Variance};
+use export::Format;
 use point::Point;
 use regex::Regex;
+use std::ops::RangeInclusive;
+use std::path::Path;
 
 type Position = Point;
 type Velocity = Point;
 
-#[derive(Debug)]
-enum Quadrant {
-    TopLeft,
-    TopRight,
-    BottomLeft,
-    BottomRight,
-}
-
-const QUADRANTS: [Quadrant; 4] = [
-    Quadrant::TopLeft,
-    Quadrant::TopRight,
-    Quadrant::BottomLeft,
-    Quadrant::BottomRight,
-];
-
 #[derive(Debug)]
 struct Robot {
     position: Position,
@@ -36,45 +27,90 @@ impl Robot {
     fn _new(position: Position, velocity: Velocity) -> Self {
         Self { position, velocity }
     }
-    fn advance(&mut self, bathroom: &Bathroom) {
-        let Bathroom { width, height } = bathroom;
-        self.position = self.position + Position::new(self.velocity.x, self.velocity.y);
-        self.position.x = self.position.x.rem_euclid(*width as i32);
-        self.position.y = self.position.y.rem_euclid(*height as i32);
-    }
 }
 
 impl Bathroom {
     fn new(width: u32, height: u32) -> Self {
         Self { width, height }
     }
-    fn in_top_quadrant(&self, point: &Point) -> bool {
-        (point.y as u32) < self.height / 2
+    // The smallest bathroom that holds every robot's starting position.
+    fn enclosing(robots: &[Robot]) -> Self {
+        let width = robots.iter().map(|r| r.position.x).max().unwrap() + 1;
+        let height = robots.iter().map(|r| r.position.y).max().unwrap() + 1;
+        Self::new(width as u32, height as u32)
     }
-    fn in_bottom_quadrant(&self, point: &Point) -> bool {
-        (point.y as u32) > self.height / 2
+    // Every robot returns to its starting position after lcm(width, height)
+    // seconds, so this is the period of the whole system.
+    fn period(&self) -> u64 {
+        let (width, height) = (self.width as u64, self.height as u64);
+        width / gcd(width, height) * height
     }
-    fn in_left_quadrant(&self, point: &Point) -> bool {
-        (point.x as u32) < self.width / 2
-    }
-    fn in_right_quadrant(&self, point: &Point) -> bool {
-        (point.x as u32) > self.width / 2
+    // Jumps straight to time t (negative t rewinds) using (p + v*t) mod (w, h).
+    // Reducing t by the period first keeps v*t well within i64.
+    fn positions_at(&self, robots: &[Robot], t: i64) -> Vec<Position> {
+        let t = t.rem_euclid(self.period() as i64);
+        let (width, height) = (self.width as i64, self.height as i64);
+        robots
+            .iter()
+            .map(|robot| {
+                let x = (robot.position.x as i64 + robot.velocity.x as i64 * t).rem_euclid(width);
+                let y = (robot.position.y as i64 + robot.velocity.y as i64 * t).rem_euclid(height);
+                Position::new(x as i32, y as i32)
+            })
+            .collect()
     }
-
-    fn in_quadrant(&self, quadrant: &Quadrant, points: &[Point]) -> u32 {
-        let test1 = match quadrant {
-            Quadrant::TopLeft | Quadrant::TopRight => Self::in_top_quadrant,
-            Quadrant::BottomLeft | Quadrant::BottomRight => Self::in_bottom_quadrant,
-        };
-        let test2 = match quadrant {
-            Quadrant::TopLeft | Quadrant::BottomLeft => Self::in_left_quadrant,
-            Quadrant::TopRight | Quadrant::BottomRight => Self::in_right_quadrant,
-        };
+    // Counts the points in one region of the bathroom when it is split into
+    // `columns` x `rows` regions, with region (0, 0) in the top left.
+    fn in_region(&self, (columns, rows): (u32, u32), region: (u32, u32), points: &[Point]) -> u32 {
         points
             .iter()
-            .filter(|p| test1(self, p) && test2(self, p))
+            .filter(|p| {
+                band(p.x as u32, self.width, columns) == Some(region.0)
+                    && band(p.y as u32, self.height, rows) == Some(region.1)
+            })
             .count() as u32
     }
+    fn safety_factor(&self, partition: (u32, u32), points: &[Point]) -> u32 {
+        let (columns, rows) = partition;
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|region| self.in_region(partition, region, points))
+            .product()
+    }
+}
+
+// Parses "WxH", as used for both the bathroom size and its regions.
+fn parse_size(txt: &str) -> Result<(u32, u32), String> {
+    txt.split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .ok_or_else(|| format!("expected WxH with W and H at least 1, got {:?}", txt))
+}
+
+// Splits an axis of `length` cells into `parts` bands and returns the band
+// holding `coord`. Equal bands are used when they fit exactly, so an even
+// sized bathroom splits cleanly in half. Otherwise, if the spare cells can
+// form dividing lines between equal bands (like the middle row and column of
+// an odd sized bathroom) those lines belong to no band. Failing both, the
+// bands differ in size by at most one cell.
+fn band(coord: u32, length: u32, parts: u32) -> Option<u32> {
+    if length.is_multiple_of(parts) {
+        return Some(coord / (length / parts));
+    }
+    let lines = parts - 1;
+    if length >= parts + lines && (length - lines).is_multiple_of(parts) {
+        let size = (length - lines) / parts;
+        return (coord % (size + 1) != size).then_some(coord / (size + 1));
+    }
+    Some(coord * parts / length)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
 fn initialise_robots(data: &[&str]) -> Vec<Robot> {
@@ -95,84 +131,285 @@ fn initialise_robots(data: &[&str]) -> Vec<Robot> {
     robots
 }
 
-fn print_bathroom(robots: &[Robot], bathroom: &Bathroom) {
-    for y in 0..bathroom.height {
-        for x in 0..bathroom.width {
-            let point = Point::new(x as i32, y as i32);
-            let num_robots = robots.iter().filter(|r| r.position == point).count();
-            if num_robots > 0 {
-                print!("{}", num_robots);
-            } else {
-                print!(".");
-            }
-        }
-        println!();
-    }
-}
-
-fn populate(robots: &Vec<Robot>, width: u32, height: u32) -> Vec<Vec<char>> {
-    let mut grid = vec![vec!['.'; width as usize]; height as usize];
-    for robot in robots {
-        grid[robot.position.y as usize][robot.position.x as usize] = '#';
+fn populate(positions: &[Position], width: u32, height: u32) -> Vec<Vec<u32>> {
+    let mut grid = vec![vec![0; width as usize]; height as usize];
+    for position in positions {
+        grid[position.y as usize][position.x as usize] += 1;
     }
     grid
 }
 
-fn _display_bathroom(grid: &[Vec<char>], bathroom: &Bathroom) {
+fn display_bathroom(grid: &[Vec<u32>], bathroom: &Bathroom) {
     for row in 0..bathroom.height {
         for col in 0..bathroom.width {
-            print!("{}", grid[row as usize][col as usize]);
+            match grid[row as usize][col as usize] {
+                0 => print!("."),
+                count => print!("{}", count),
+            }
         }
         println!();
     }
 }
 
-fn solve_part1(data: &Vec<&str>) -> u32 {
-    let mut robots = initialise_robots(data);
-    let bathroom = Bathroom::new(101, 103);
+// Writes the selected frames into dir, either one image per frame or a
+// single animated GIF. Frames are computed and written one at a time, so a
+// range covering a whole period doesn't have to fit in memory.
+fn export_frames(
+    robots: &[Robot],
+    bathroom: &Bathroom,
+    format: Format,
+    frames: &[RangeInclusive<i64>],
+    dir: &Path,
+) -> std::io::Result<()> {
+    let grid = |t: i64| {
+        populate(
+            &bathroom.positions_at(robots, t),
+            bathroom.width,
+            bathroom.height,
+        )
+    };
+    let times = || frames.iter().cloned().flatten();
 
-    for _ in 0..100 {
-        robots.iter_mut().for_each(|robot| robot.advance(&bathroom));
+    std::fs::create_dir_all(dir)?;
+    let frame = |t: i64, extension: &str| dir.join(format!("frame-{t}.{extension}"));
+    match format {
+        Format::Ppm => times().try_for_each(|t| export::write_ppm(&frame(t, "ppm"), &grid(t))),
+        Format::Png => times().try_for_each(|t| export::write_png(&frame(t, "png"), &grid(t))),
+        Format::Gif => {
+            let first = times().next().unwrap_or_default();
+            let last = frames.last().map_or(first, |range| *range.end());
+            export::write_gif(
+                &dir.join(format!("frames-{first}-{last}.gif")),
+                times().map(grid),
+            )
+        }
     }
+}
 
-    let final_positions: Vec<Point> = robots.iter().map(|r| r.position).collect();
-    QUADRANTS
-        .iter()
-        .map(|q| bathroom.in_quadrant(q, &final_positions))
-        .product()
+fn solve_part1(robots: &[Robot], bathroom: &Bathroom, partition: (u32, u32)) -> u32 {
+    let final_positions = bathroom.positions_at(robots, 100);
+    bathroom.safety_factor(partition, &final_positions)
 }
 
-fn detect_easter_egg(grid: &[Vec<char>], bathroom: &Bathroom) -> bool {
-    let line_width = 30;
-    for y in 0..bathroom.height {
-        for x in 0..bathroom.width - line_width {
-            if (0..line_width).all(|i| grid[y as usize][x as usize + i as usize] == '#') {
-                return true;
-            }
+fn solve_part2(robots: &[Robot], bathroom: &Bathroom, detector: &dyn Detector) -> Option<u64> {
+    let detection = detector.detect(bathroom, robots)?;
+    let positions = bathroom.positions_at(robots, detection.frame as i64);
+    display_bathroom(
+        &populate(&positions, bathroom.width, bathroom.height),
+        bathroom,
+    );
+    println!(
+        "{}: confidence {:.1}",
+        detector.name(),
+        detection.confidence
+    );
+    Some(detection.frame)
+}
+
+// Removes `flag` from args, reporting whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let found = args.iter().position(|arg| arg == flag);
+    found.inspect(|&i| {
+        args.remove(i);
+    });
+    found.is_some()
+}
+
+// Removes `option` and the value following it from args.
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+fn main() {
+    // cargo run -- [--example] [--size WxH] [--regions NxM]
+    // cargo run -- [--example] [--size WxH] export <ppm|png|gif> <frames> <dir>
+    // where frames is a list of frames and ranges like 0..=100,7520.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = if take_flag(&mut args, "--example") {
+        include_str!("example.txt")
+    } else {
+        include_str!("puzzle.txt")
+    };
+    let data: Vec<&str> = input.lines().collect();
+    let robots = initialise_robots(&data);
+    let size = |option: &str, txt: String| {
+        parse_size(&txt).unwrap_or_else(|e| {
+            eprintln!("{}: {}", option, e);
+            std::process::exit(1);
+        })
+    };
+    let bathroom = match take_option(&mut args, "--size") {
+        Some(txt) => {
+            let (width, height) = size("--size", txt);
+            Bathroom::new(width, height)
+        }
+        None => {
+            // A guess: it comes out too small if no robot starts in the last
+            // row or column, so show it rather than use it silently.
+            let bathroom = Bathroom::enclosing(&robots);
+            eprintln!(
+                "No --size given, using {}x{} from the robots' starting positions",
+                bathroom.width, bathroom.height
+            );
+            bathroom
+        }
+    };
+    let partition =
+        take_option(&mut args, "--regions").map_or((2, 2), |txt| size("--regions", txt));
+
+    if args.len() == 4 && args[0] == "export" {
+        let export = || {
+            let format = args[1].parse()?;
+            let frames = export::parse_frames(&args[2])?;
+            export_frames(&robots, &bathroom, format, &frames, Path::new(&args[3]))
+        };
+        if let Err(e) = export() {
+            eprintln!("Export failed: {}", e);
+            std::process::exit(1);
         }
+        return;
+    }
+
+    println!("Part 1:{:?}", solve_part1(&robots, &bathroom, partition));
+    match solve_part2(&robots, &bathroom, &ChineseRemainder) {
+        Some(frame) => println!("Part 2:{}", frame),
+        None => println!("Part 2: no picture detected"),
+    }
+
+    let detectors: [&dyn Detector; 3] = [&Variance, &Entropy, &SafetyFactor];
+    for detector in detectors {
+        println!(
+            "{}: {:?}",
+            detector.name(),
+            detector.detect(&bathroom, &robots)
+        );
     }
-    false
 }
 
-fn solve_part2(data: &Vec<&str>) -> Option<u32> {
-    let mut robots = initialise_robots(data);
-    let bathroom = Bathroom::new(101, 103);
-    let mut grid : Vec<Vec<char>>;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> (Vec<Robot>, Bathroom) {
+        let data: Vec<&str> = include_str!("example.txt").lines().collect();
+        (initialise_robots(&data), Bathroom::new(11, 7))
+    }
+
+    // Moves every robot one second at a time, forwards or backwards.
+    fn simulate(robots: &[Robot], bathroom: &Bathroom, t: i64) -> Vec<Position> {
+        let (width, height) = (bathroom.width as i32, bathroom.height as i32);
+        let direction = t.signum() as i32;
+        robots
+            .iter()
+            .map(|robot| {
+                let mut position = robot.position;
+                for _ in 0..t.abs() {
+                    position.x = (position.x + direction * robot.velocity.x).rem_euclid(width);
+                    position.y = (position.y + direction * robot.velocity.y).rem_euclid(height);
+                }
+                position
+            })
+            .collect()
+    }
+
+    #[test]
+    fn positions_match_simulation_forwards_and_backwards() {
+        let (robots, bathroom) = example();
+        for t in [-200, -78, -77, -1, 0, 1, 76, 77, 78, 200] {
+            assert_eq!(
+                bathroom.positions_at(&robots, t),
+                simulate(&robots, &bathroom, t),
+                "t = {t}"
+            );
+        }
+    }
 
-    for n in 1..10000 {
-        robots.iter_mut().for_each(|robot| robot.advance(&bathroom));
-        grid = populate(&robots, bathroom.width, bathroom.height);
-        if detect_easter_egg(&grid, &bathroom) {
-            print_bathroom(&robots, &bathroom);
-            return Some(n);
+    #[test]
+    fn positions_repeat_every_period() {
+        let (robots, bathroom) = example();
+        let period = bathroom.period() as i64;
+        assert_eq!(period, 77);
+        for t in -100..100 {
+            assert_eq!(
+                bathroom.positions_at(&robots, t + period),
+                bathroom.positions_at(&robots, t)
+            );
         }
+        assert_eq!(
+            bathroom.positions_at(&robots, i64::MAX),
+            bathroom.positions_at(&robots, i64::MAX % period)
+        );
+        assert_eq!(
+            bathroom.positions_at(&robots, i64::MIN),
+            bathroom.positions_at(&robots, i64::MIN.rem_euclid(period))
+        );
     }
-    None
-}
 
-fn main() {
-    let data: Vec<&str> = include_str!("puzzle.txt").lines().collect();
+    #[test]
+    fn parse_formats_and_frames() {
+        assert_eq!("gif".parse::<Format>().unwrap(), Format::Gif);
+        assert!("bmp".parse::<Format>().is_err());
+        assert_eq!(
+            export::parse_frames("0..=3, 7520,-2..=-1").unwrap(),
+            [0..=3, 7520..=7520, -2..=-1]
+        );
+        assert!(export::parse_frames("5..=2").is_err());
+        assert!(export::parse_frames("5..7").is_err());
+        assert!(export::parse_frames("").is_err());
+    }
+
+    #[test]
+    fn export_writes_only_the_selected_frames() {
+        let (robots, bathroom) = example();
+        let dir = std::env::temp_dir().join(format!("day14-export-{}", std::process::id()));
+        let frames = export::parse_frames("3,0..=1").unwrap();
+        export_frames(&robots, &bathroom, Format::Ppm, &frames, &dir).unwrap();
+        let mut written: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        written.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, ["frame-0.ppm", "frame-1.ppm", "frame-3.ppm"]);
+    }
+
+    #[test]
+    fn example_safety_factor() {
+        let (robots, bathroom) = example();
+        assert_eq!(solve_part1(&robots, &bathroom, (2, 2)), 12);
+    }
+
+    #[test]
+    fn band_splits_even_sizes_in_half() {
+        let bands: Vec<_> = (0..10).map(|coord| band(coord, 10, 2)).collect();
+        assert_eq!(bands, [[Some(0); 5], [Some(1); 5]].concat());
+    }
+
+    #[test]
+    fn band_leaves_out_the_middle_of_odd_sizes() {
+        let bands: Vec<_> = (0..7).map(|coord| band(coord, 7, 2)).collect();
+        assert_eq!(
+            bands,
+            [Some(0), Some(0), Some(0), None, Some(1), Some(1), Some(1)]
+        );
+        // 11 = 3 + 1 + 3 + 1 + 3, with dividing lines at 3 and 7.
+        let bands: Vec<_> = (0..11).map(|coord| band(coord, 11, 3)).collect();
+        assert_eq!(
+            bands,
+            [0, 0, 0, 9, 1, 1, 1, 9, 2, 2, 2].map(|b| (b != 9).then_some(b))
+        );
+        // 10 can't be split into three with dividing lines, so bands differ by one.
+        let bands: Vec<_> = (0..10).map(|coord| band(coord, 10, 3)).collect();
+        assert_eq!(bands, [0, 0, 0, 0, 1, 1, 1, 2, 2, 2].map(Some));
+    }
 
-    println!("Part 1:{:?}", solve_part1(&data));
-    println!("Part 2:{:?}", solve_part2(&data).unwrap());
+    #[test]
+    fn sizes_must_not_be_zero() {
+        assert_eq!(parse_size("11x7"), Ok((11, 7)));
+        assert!(parse_size("0x5").is_err());
+        assert!(parse_size("2x0").is_err());
+        assert!(parse_size("11").is_err());
+    }
 }
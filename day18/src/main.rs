@@ -79,6 +79,38 @@ impl Memory {
     fn drop_bytes(&mut self, bytes: &Vec<&Point>) {
         bytes.iter().for_each(|point| self.set(**point, Tile::Wall));
     }
+    // Byte i (counting from 1) lands at the end of step i, so a cell is
+    // only safe to enter while the walker arrives before its byte does.
+    fn drop_bytes_over_time(&mut self, bytes: &[Point]) {
+        bytes
+            .iter()
+            .enumerate()
+            .rev()
+            .for_each(|(i, point)| self.set(*point, Tile::Falling(i as u32 + 1)));
+    }
+    fn search_timed(&mut self) -> Option<u32> {
+        let mut queue = PriorityQueue::new();
+        queue.push(self.start, Reverse(0));
+        self.set(self.start, Tile::Floor(Some(0)));
+        while let Some((point, Reverse(time))) = queue.pop() {
+            if point == self.end {
+                return Some(time);
+            }
+            let arrival = time + 1;
+            self.neighbours(point).iter().for_each(|neighbour| {
+                let open = match self.get(*neighbour) {
+                    Some(Tile::Floor(value)) => value.is_none_or(|seen| seen > arrival),
+                    Some(Tile::Falling(landing)) => arrival < landing,
+                    _ => false,
+                };
+                if open {
+                    self.set(*neighbour, Tile::Floor(Some(arrival)));
+                    queue.push(*neighbour, Reverse(arrival));
+                }
+            })
+        }
+        None
+    }
     fn _dump(&self) {
         self.layout.iter().for_each(|row| {
             for tile in row {
@@ -106,6 +138,13 @@ fn main() {
     memory.drop_bytes(&bytes);
     println!("Part 1: {}", memory.search().unwrap());
 
+    let mut memory = Memory::new(width as usize, height as usize);
+    memory.drop_bytes_over_time(&data);
+    match memory.search_timed() {
+        Some(steps) => println!("Falling bytes: {}", steps),
+        None => println!("Falling bytes: no escape"),
+    }
+
     loop {
         amount += 1;
         let mut memory = Memory::new(width as usize, height as usize);
@@ -117,3 +156,43 @@ fn main() {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(width: usize, height: usize, bytes: &[(i32, i32)]) -> Option<u32> {
+        let bytes: Vec<Point> = bytes.iter().map(|&byte| byte.into()).collect();
+        let mut memory = Memory::new(width, height);
+        memory.drop_bytes_over_time(&bytes);
+        memory.search_timed()
+    }
+
+    // .1...
+    // ...2.
+    // ...3.
+    // ..4..
+    // .5...
+    // Down the left edge and along the bottom is the only 8-step route once
+    // bytes 2 to 4 have landed, and byte 5 lands on it at step 5.
+    const BYTES: [(i32, i32); 5] = [(1, 0), (3, 1), (3, 2), (2, 3), (1, 4)];
+
+    #[test]
+    fn byte_landing_as_the_walker_arrives_forces_a_detour() {
+        assert_eq!(escape(4, 4, &BYTES[..4]), Some(8));
+        assert_eq!(escape(4, 4, &BYTES), Some(10));
+    }
+
+    #[test]
+    fn byte_landing_after_the_walker_passes_is_harmless() {
+        // A byte at (4, 0), off the route, delays the last one by a step.
+        let mut bytes = BYTES.to_vec();
+        bytes.insert(4, (4, 0));
+        assert_eq!(escape(4, 4, &bytes), Some(8));
+    }
+
+    #[test]
+    fn no_escape_when_the_exit_is_walled_in() {
+        assert_eq!(escape(4, 4, &[(3, 4), (4, 3)]), None);
+    }
+}
@@ -4,6 +4,7 @@ use std::fmt::Display;
 pub enum Tile {
     Wall,
     Floor(Option<u32>),
+    Falling(u32),
 }
 
 impl Display for Tile {
@@ -17,6 +18,7 @@ impl Display for Tile {
                 write!(f, ".  ")
               }
             },
+            Tile::Falling(time) => write!(f, "@{time:<2}"),
         }
     }
 }
\ No newline at end of file
@@ -1,52 +1,49 @@
-fn calculate(nums: &mut [u64], acc: &mut Vec<u64>, operators: &[&str]) {
-    if nums.len() == 1 {
-        acc.push(nums[0]);
-        return;
-    }
-    let first = nums[0];
-    let second = nums[1];
-    let nums = nums[2..].to_vec();
-
-    for op in operators {
-        let mut nums = nums.clone();
-        let result = match *op {
-            "+" => first + second,
-            "*" => first * second,
-            "||" => format!("{}{}", first, second).parse().unwrap(),
-            _ => 0,
-        };
-        nums.insert(0, result);
-        calculate(&mut nums, acc, operators);
+// Works backwards from the target, undoing the last operator at each step:
+// subtracting for +, dividing for * when it divides exactly, and stripping
+// the last number's digits off the end for ||. Any operator that can't be
+// undone prunes that branch, and the search stops at the first match.
+fn solvable(target: u64, nums: &[u64], operators: &[&str]) -> bool {
+    match nums {
+        [] => false,
+        [first] => *first == target,
+        [rest @ .., last] => operators.iter().any(|op| match *op {
+            "+" => target >= *last && solvable(target - last, rest, operators),
+            // anything times zero is zero
+            "*" if *last == 0 => target == 0,
+            "*" => target.is_multiple_of(*last) && solvable(target / last, rest, operators),
+            "||" => strip_suffix(target, *last).is_some_and(|t| solvable(t, rest, operators)),
+            _ => false,
+        }),
     }
 }
 
-fn solve(equations: &mut [(u64, Vec<u64>)], operators: &[&str]) -> u64 {
-    equations.iter_mut().map(|(total, nums)| {
-        let mut acc = vec![];
-        calculate(nums, &mut acc, &operators);
-        if acc.contains(total) {
-            *total
-        }  else {
-            0
-        }
-    })
-    .sum::<u64>()
+// The number left after removing `suffix` from the end of `num`'s digits.
+fn strip_suffix(num: u64, suffix: u64) -> Option<u64> {
+    let base = 10u64.pow(suffix.checked_ilog10().unwrap_or(0) + 1);
+    (num % base == suffix).then_some(num / base)
 }
 
-fn solve_part1(equations: &mut [(u64, Vec<u64>)]) -> u64 {
+fn solve(equations: &[(u64, Vec<u64>)], operators: &[&str]) -> u64 {
+    equations
+        .iter()
+        .filter(|(total, nums)| solvable(*total, nums, operators))
+        .map(|(total, _)| total)
+        .sum()
+}
+
+fn solve_part1(equations: &[(u64, Vec<u64>)]) -> u64 {
     let operators = vec!["+", "*"];
     solve(equations, &operators)
 }
 
-fn solve_part2(equations: &mut [(u64, Vec<u64>)]) -> u64 {
+fn solve_part2(equations: &[(u64, Vec<u64>)]) -> u64 {
     let operators = vec!["+", "*", "||"];
     solve(equations, &operators)
 }
 
-fn main() {
-    let lines: Vec<&str> = include_str!("puzzle.txt").lines().collect();
-    let mut equations: Vec<(u64, Vec<u64>)> = lines
-        .iter()
+fn parse_equations(input: &str) -> Vec<(u64, Vec<u64>)> {
+    input
+        .lines()
         .map(|s| s.split_once(": ").unwrap())
         .map(|(total, nums)| {
             (
@@ -56,8 +53,51 @@ fn main() {
                     .collect::<Vec<u64>>(),
             )
         })
-        .collect();
+        .collect()
+}
+
+fn main() {
+    let equations = parse_equations(include_str!("puzzle.txt"));
 
-    println!("Part 1: {}", solve_part1(&mut equations));
-    println!("Part 2: {}", solve_part2(&mut equations));
+    println!("Part 1: {}", solve_part1(&equations));
+    println!("Part 2: {}", solve_part2(&equations));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [&str; 3] = ["+", "*", "||"];
+
+    #[test]
+    fn example_answers() {
+        let equations = parse_equations(include_str!("example.txt"));
+        assert_eq!(solve_part1(&equations), 3749);
+        assert_eq!(solve_part2(&equations), 11387);
+    }
+
+    #[test]
+    fn zero_operands() {
+        assert!(solvable(0, &[5, 0], &["*"]));
+        assert!(solvable(5, &[5, 0], &["+"]));
+        assert!(solvable(50, &[5, 0], &["||"]));
+        // 0 || 6 is just 6.
+        assert!(solvable(6, &[0, 6], &["||"]));
+        assert!(!solvable(7, &[0, 6], &ALL));
+        // A zero in the middle wipes out everything before it.
+        assert!(solvable(0, &[7, 0, 3], &["*"]));
+        assert!(solvable(3, &[7, 0, 3], &["+", "*"]));
+        assert!(!solvable(3, &[7, 0, 3], &["*"]));
+    }
+
+    #[test]
+    fn target_ending_in_the_last_operand() {
+        assert!(solvable(156, &[15, 6], &ALL));
+        assert!(!solvable(156, &[15, 6], &ALL[..2]));
+        // Stripping 6 from 1006 leaves 100, so only + works.
+        assert!(solvable(1006, &[1000, 6], &ALL));
+        assert!(!solvable(1006, &[1000, 6], &["||"]));
+        // The last operand has more digits than the target.
+        assert!(!solvable(6, &[0, 16], &["||"]));
+    }
 }
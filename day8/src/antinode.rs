@@ -0,0 +1,133 @@
+use crate::point::{Point, Point3};
+use std::ops::{Add, Sub};
+
+// A point on an integer lattice in any number of dimensions.
+pub trait Lattice: Copy + PartialEq + Add<Output = Self> + Sub<Output = Self> {
+    fn scale(self, factor: i32) -> Self;
+    // Divides every component by `divisor`, if they all divide exactly.
+    fn divide(self, divisor: i32) -> Option<Self>;
+    // The greatest common divisor of the components.
+    fn gcd(self) -> i32;
+}
+
+impl Lattice for Point {
+    fn scale(self, factor: i32) -> Self {
+        Point::new(self.row * factor, self.col * factor)
+    }
+    fn divide(self, divisor: i32) -> Option<Self> {
+        (self.row % divisor == 0 && self.col % divisor == 0)
+            .then(|| Point::new(self.row / divisor, self.col / divisor))
+    }
+    fn gcd(self) -> i32 {
+        gcd(self.row, self.col)
+    }
+}
+
+impl Lattice for Point3 {
+    fn scale(self, factor: i32) -> Self {
+        Point3::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+    fn divide(self, divisor: i32) -> Option<Self> {
+        (self.x % divisor == 0 && self.y % divisor == 0 && self.z % divisor == 0)
+            .then(|| Point3::new(self.x / divisor, self.y / divisor, self.z / divisor))
+    }
+    fn gcd(self) -> i32 {
+        gcd(gcd(self.x, self.y), self.z)
+    }
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Antinodes {
+    // Points in line with both antennas where one antenna is `far / near`
+    // times as far away as the other. There is always one beyond each
+    // antenna, and with `between` set there can also be points between the
+    // two antennas when the separation divides evenly.
+    Ratio { near: i32, far: i32, between: bool },
+    // Every whole multiple of the separation out from either antenna.
+    Harmonics,
+    // Every lattice point in line with both antennas: the separation reduced
+    // by its gcd, so points between the antennas are included. This is part 2.
+    Lattice,
+}
+
+impl Antinodes {
+    // The puzzle's rule: one antinode on either side of each pair.
+    pub const PART1: Antinodes = Antinodes::Ratio {
+        near: 1,
+        far: 2,
+        between: false,
+    };
+
+    // The antinodes of one pair of antennas. `in_bounds` must reject points
+    // far enough out, or the harmonic walks never end.
+    pub fn of_pair<P: Lattice>(&self, a: P, b: P, in_bounds: impl Fn(&P) -> bool) -> Vec<P> {
+        let diff = b - a;
+        let step = match self {
+            Antinodes::Ratio { near, far, between } => {
+                // P = a + t(b - a) with |t| : |t - 1| being near:far or far:near.
+                // Beyond the antennas t = near / (near - far) or far / (far - near),
+                // and between them t = near / (near + far) or far / (near + far).
+                let mut fractions = vec![(*near, near - far), (*far, far - near)];
+                if *between {
+                    fractions.extend([(*near, near + far), (*far, near + far)]);
+                }
+                let mut antinodes: Vec<P> = Vec::new();
+                for (numerator, denominator) in fractions {
+                    if denominator == 0 {
+                        continue;
+                    }
+                    if let Some(offset) = diff.scale(numerator).divide(denominator) {
+                        let p = a + offset;
+                        if in_bounds(&p) && !antinodes.contains(&p) {
+                            antinodes.push(p);
+                        }
+                    }
+                }
+                return antinodes;
+            }
+            Antinodes::Harmonics => diff,
+            Antinodes::Lattice => match diff.gcd() {
+                0 => diff,
+                gcd => diff.divide(gcd).unwrap(),
+            },
+        };
+        if step == diff.scale(0) {
+            return vec![a].into_iter().filter(|p| in_bounds(p)).collect();
+        }
+        let mut antinodes = Vec::new();
+        let mut p = a;
+        while in_bounds(&p) {
+            antinodes.push(p);
+            p = p + step;
+        }
+        p = a - step;
+        while in_bounds(&p) {
+            antinodes.push(p);
+            p = p - step;
+        }
+        antinodes
+    }
+
+    // The antinodes of every pair among antennas of one frequency.
+    pub fn of_antennas<P: Lattice>(
+        &self,
+        antennas: &[P],
+        in_bounds: impl Fn(&P) -> bool,
+    ) -> Vec<P> {
+        let mut antinodes = Vec::new();
+        for (i, a) in antennas.iter().enumerate() {
+            for b in &antennas[i + 1..] {
+                antinodes.extend(self.of_pair(*a, *b, &in_bounds));
+            }
+        }
+        antinodes
+    }
+}
@@ -1,7 +1,9 @@
+mod antinode;
 mod point;
 
 use std::collections::{HashMap, HashSet};
 
+use antinode::Antinodes;
 use point::Point;
 
 #[derive(Debug)]
@@ -11,13 +13,84 @@ struct City {
     height: u32,
 }
 
+// Rows and columns are numbered from 1.
+#[derive(Debug)]
+enum ParseError {
+    Empty,
+    Ragged {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    BadCell {
+        row: usize,
+        col: usize,
+        cell: char,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "the map is empty"),
+            ParseError::Ragged {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {}: expected {} cells, found {}",
+                row, expected, found
+            ),
+            ParseError::BadCell { row, col, cell } => {
+                write!(
+                    f,
+                    "row {}, column {}: {:?} is not an antenna",
+                    row, col, cell
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 impl City {
-    fn new(grid: Vec<Vec<char>>) -> Self {
-        City {
-            width: grid[0].len() as u32,
+    // Checks the map is a non-empty rectangle of `.` and antennas, which are
+    // single letters or digits.
+    fn parse(input: &str) -> Result<Self, ParseError> {
+        let grid: Vec<Vec<char>> = input
+            .lines()
+            .map(|line| line.trim_end().chars().collect())
+            .collect();
+        let width = grid.first().map_or(0, Vec::len);
+        if width == 0 {
+            return Err(ParseError::Empty);
+        }
+        for (row, cells) in grid.iter().enumerate() {
+            if cells.len() != width {
+                return Err(ParseError::Ragged {
+                    row: row + 1,
+                    expected: width,
+                    found: cells.len(),
+                });
+            }
+            if let Some(col) = cells
+                .iter()
+                .position(|c| *c != '.' && !c.is_ascii_alphanumeric())
+            {
+                return Err(ParseError::BadCell {
+                    row: row + 1,
+                    col: col + 1,
+                    cell: cells[col],
+                });
+            }
+        }
+        Ok(City {
+            width: width as u32,
             height: grid.len() as u32,
             grid,
-        }
+        })
     }
 
     fn is_valid(&self, point: &Point) -> bool {
@@ -43,79 +116,310 @@ impl City {
         antennas
     }
 
-    fn calculate_antenode_positions(
+    fn antinodes(
         &self,
         antennas: &HashMap<char, Vec<Point>>,
         frequency: char,
+        rule: Antinodes,
     ) -> Vec<Point> {
-        let points = antennas.get(&frequency).unwrap();
-        let mut antenodes: Vec<Point> = Vec::new();
-        for i in 0..points.len() - 1 {
-            for j in i + 1..points.len() {
-                let p1 = points[i];
-                let p2 = points[j];
-                let diff = p1 - p2;
-                antenodes.push(p1 + diff);
-                antenodes.push(p2 - diff);
-            }
+        match antennas.get(&frequency) {
+            Some(points) => rule.of_antennas(points, |p| self.is_valid(p)),
+            None => Vec::new(),
         }
-        antenodes.into_iter().filter(|p| self.is_valid(p)).collect()
     }
 
-    fn calculate_antenode_positions_with_harmonics(
-        &self,
-        antennas: &HashMap<char, Vec<Point>>,
-        frequency: char,
-    ) -> Vec<Point> {
-        let points = antennas.get(&frequency).unwrap();
-        let mut antenodes: Vec<Point> = Vec::new();
-        for i in 0..points.len() - 1 {
-            for j in i + 1..points.len() {
-                let p1 = points[i];
-                let p2 = points[j];
-                let diff = p1 - p2;
-                let mut p = p1;
-                while self.is_valid(&p) {
-                    antenodes.push(p);
-                    p = p + diff;
-                }
-                p = p2;
-                while self.is_valid(&p) {
-                    antenodes.push(p);
-                    p = p - diff;
+    fn antinodes_by_frequency(&self, rule: Antinodes) -> HashMap<char, HashSet<Point>> {
+        let antennas = self.find_antennas();
+        antennas
+            .keys()
+            .map(|key| {
+                (
+                    *key,
+                    self.antinodes(&antennas, *key, rule).into_iter().collect(),
+                )
+            })
+            .collect()
+    }
+
+    // Draws antinodes as `#` the way the puzzle does, leaving any antenna on
+    // the same spot visible. With a frequency given, antennas of every other
+    // frequency are left out.
+    fn render(&self, antinodes: &HashSet<Point>, frequency: Option<char>) -> String {
+        let mut output = String::new();
+        for (row, line) in self.grid.iter().enumerate() {
+            for (col, c) in line.iter().enumerate() {
+                let shown = *c != '.' && frequency.is_none_or(|f| f == *c);
+                if shown {
+                    output.push(*c);
+                } else if antinodes.contains(&Point::new(row as i32, col as i32)) {
+                    output.push('#');
+                } else {
+                    output.push('.');
                 }
             }
+            output.push('\n');
         }
-        antenodes
+        output
     }
 }
 
-fn solve_part1(city: &City) -> usize {
+// Antinode counts per frequency, followed by every pair of frequencies that
+// share antinode locations.
+// A frequency with a single antenna is listed with no antinodes.
+fn report(by_frequency: &HashMap<char, HashSet<Point>>) -> String {
+    let mut frequencies: Vec<char> = by_frequency.keys().copied().collect();
+    frequencies.sort();
+    let mut output = String::new();
+    for frequency in &frequencies {
+        output += &format!(
+            "'{}': {} antinodes\n",
+            frequency,
+            by_frequency[frequency].len()
+        );
+    }
+    for (i, a) in frequencies.iter().enumerate() {
+        for b in &frequencies[i + 1..] {
+            let shared = by_frequency[a].intersection(&by_frequency[b]).count();
+            if shared > 0 {
+                output += &format!("'{}' and '{}': {} shared\n", a, b, shared);
+            }
+        }
+    }
+    let mut seen: HashMap<Point, usize> = HashMap::new();
+    for point in by_frequency.values().flatten() {
+        *seen.entry(*point).or_default() += 1;
+    }
+    let overlapping = seen.values().filter(|count| **count > 1).count();
+    output += &format!(
+        "{} distinct antinodes, {} from more than one frequency\n",
+        seen.len(),
+        overlapping
+    );
+    output
+}
+
+fn count_antinodes(city: &City, rule: Antinodes) -> usize {
     let antennas = city.find_antennas();
     antennas
         .keys()
-        .flat_map(|key| city.calculate_antenode_positions(&antennas, *key))
+        .flat_map(|key| city.antinodes(&antennas, *key, rule))
         .collect::<HashSet<Point>>()
         .len()
 }
 
+fn solve_part1(city: &City) -> usize {
+    count_antinodes(city, Antinodes::PART1)
+}
+
 fn solve_part2(city: &City) -> usize {
-    let antennas = city.find_antennas();
-    antennas
-        .keys()
-        .flat_map(|key| city.calculate_antenode_positions_with_harmonics(&antennas, *key))
-        .collect::<HashSet<Point>>()
-        .len()
+    count_antinodes(city, Antinodes::Lattice)
+}
+
+// Removes `flag` from args, reporting whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let found = args.iter().position(|arg| arg == flag);
+    found.inspect(|&i| {
+        args.remove(i);
+    });
+    found.is_some()
+}
+
+// Removes `option` and the value following it from args.
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.remove(i);
+    Some(args.remove(i))
 }
 
 fn main() {
-    let grid: Vec<Vec<char>> = include_str!("puzzle.txt")
-        .lines()
-        .map(|line| line.chars().collect())
-        .collect();
+    // cargo run -- [--example] [--rule <part1|between|multiples|lattice>]
+    //     [report | render [--frequency <char>]]
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = if take_flag(&mut args, "--example") {
+        include_str!("example.txt")
+    } else {
+        include_str!("puzzle.txt")
+    };
+    let rule = match take_option(&mut args, "--rule").as_deref() {
+        None | Some("part1") => Antinodes::PART1,
+        Some("between") => Antinodes::Ratio {
+            near: 1,
+            far: 2,
+            between: true,
+        },
+        Some("multiples") => Antinodes::Harmonics,
+        Some("lattice") => Antinodes::Lattice,
+        Some(rule) => panic!("Unknown rule: {}", rule),
+    };
+    let frequency = take_option(&mut args, "--frequency").map(|f| f.chars().next().unwrap());
+
+    let city = City::parse(input).unwrap_or_else(|e| panic!("{}", e));
+    if let Some(f) = frequency.filter(|f| !city.find_antennas().contains_key(f)) {
+        eprintln!("No antennas on frequency '{}'", f);
+    }
 
-    let city = City::new(grid);
+    match args.first().map(String::as_str) {
+        Some("report") => {
+            print!("{}", report(&city.antinodes_by_frequency(rule)));
+            return;
+        }
+        Some("render") => {
+            let by_frequency = city.antinodes_by_frequency(rule);
+            let antinodes: HashSet<Point> = by_frequency
+                .iter()
+                .filter(|(key, _)| frequency.is_none_or(|f| f == **key))
+                .flat_map(|(_, points)| points.iter().copied())
+                .collect();
+            print!("{}", city.render(&antinodes, frequency));
+            return;
+        }
+        _ => {}
+    }
 
     println!("Part 1: {:?}", solve_part1(&city));
     println!("Part 2: {:?}", solve_part2(&city));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use point::Point3;
+
+    fn puzzle() -> City {
+        City::parse(include_str!("puzzle.txt")).unwrap()
+    }
+
+    // Lifts the city onto the z = 0 plane of a 3D space, which should make no
+    // difference to the antinodes found.
+    fn count_antinodes_3d(city: &City, rule: Antinodes) -> usize {
+        let in_bounds = |p: &Point3| p.z == 0 && city.is_valid(&Point::new(p.x, p.y));
+        city.find_antennas()
+            .values()
+            .flat_map(|points| {
+                let points: Vec<Point3> = points
+                    .iter()
+                    .map(|p| Point3::new(p.row, p.col, 0))
+                    .collect();
+                rule.of_antennas(&points, in_bounds)
+            })
+            .collect::<HashSet<Point3>>()
+            .len()
+    }
+
+    #[test]
+    fn example_answers() {
+        let city = City::parse(include_str!("example.txt")).unwrap();
+        assert_eq!(solve_part1(&city), 14);
+        assert_eq!(solve_part2(&city), 34);
+    }
+
+    // No pair on the puzzle shares a common factor in its separation, so
+    // walking whole multiples finds every lattice point.
+    #[test]
+    fn harmonics_match_lattice_on_puzzle() {
+        let city = puzzle();
+        assert_eq!(
+            count_antinodes(&city, Antinodes::Harmonics),
+            count_antinodes(&city, Antinodes::Lattice)
+        );
+    }
+
+    // Antinodes of one pair along row 0, as a set of columns.
+    fn columns(rule: Antinodes, a: i32, b: i32, cols: std::ops::Range<i32>) -> HashSet<i32> {
+        let in_bounds = |p: &Point| p.row == 0 && cols.contains(&p.col);
+        rule.of_pair(Point::new(0, a), Point::new(0, b), in_bounds)
+            .iter()
+            .map(|p| p.col)
+            .collect()
+    }
+
+    #[test]
+    fn lattice_reduces_the_separation_by_its_gcd() {
+        assert_eq!(
+            columns(Antinodes::Harmonics, 0, 2, 0..5),
+            HashSet::from([0, 2, 4])
+        );
+        assert_eq!(
+            columns(Antinodes::Lattice, 0, 2, 0..5),
+            HashSet::from([0, 1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn between_adds_inner_points_when_the_separation_divides() {
+        let ratio = |between| Antinodes::Ratio {
+            near: 1,
+            far: 2,
+            between,
+        };
+        assert_eq!(columns(ratio(false), 0, 3, -5..10), HashSet::from([-3, 6]));
+        assert_eq!(
+            columns(ratio(true), 0, 3, -5..10),
+            HashSet::from([-3, 1, 2, 6])
+        );
+        // Two apart, the inner points would sit at a third and two thirds.
+        assert_eq!(columns(ratio(true), 0, 2, -5..10), HashSet::from([-2, 4]));
+    }
+
+    #[test]
+    fn lifting_into_3d_changes_nothing() {
+        let city = puzzle();
+        for rule in [Antinodes::PART1, Antinodes::Lattice] {
+            assert_eq!(
+                count_antinodes(&city, rule),
+                count_antinodes_3d(&city, rule)
+            );
+        }
+    }
+
+    #[test]
+    fn lone_antenna_has_no_antinodes() {
+        let city = City::parse("....\n.a..\n....\n").unwrap();
+        let antennas = city.find_antennas();
+        for rule in [Antinodes::PART1, Antinodes::Harmonics, Antinodes::Lattice] {
+            assert!(city.antinodes(&antennas, 'a', rule).is_empty());
+            assert_eq!(count_antinodes(&city, rule), 0);
+        }
+    }
+
+    #[test]
+    fn missing_frequency_has_no_antinodes() {
+        let city = City::parse("....\n....\n").unwrap();
+        let antennas = city.find_antennas();
+        assert!(antennas.is_empty());
+        assert!(city.antinodes(&antennas, 'z', Antinodes::PART1).is_empty());
+        assert!(city
+            .antinodes(&antennas, 'z', Antinodes::Lattice)
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_map() {
+        assert!(matches!(City::parse(""), Err(ParseError::Empty)));
+        assert!(matches!(City::parse("\n\n"), Err(ParseError::Empty)));
+    }
+
+    #[test]
+    fn rejects_ragged_map() {
+        assert!(matches!(
+            City::parse("....\n....\n.a.\n"),
+            Err(ParseError::Ragged {
+                row: 3,
+                expected: 4,
+                found: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_cell() {
+        assert!(matches!(
+            City::parse("....\n..#.\n"),
+            Err(ParseError::BadCell {
+                row: 2,
+                col: 3,
+                cell: '#'
+            })
+        ));
+    }
+}
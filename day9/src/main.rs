@@ -1,19 +1,73 @@
-use std::collections::VecDeque;
+mod strategy;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+// Each digit of the disk map is a single decimal digit.
+const MAX_SPAN: usize = 9;
 
 #[derive(Debug, Clone, Copy)]
 struct Block {
-    id: Option<u32>,
-    size: u32,
+    id: Option<u64>,
+    size: u64,
+}
+
+// One step of a compaction: `length` cells of file `id` moved from one
+// position on the disk to another.
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    id: u64,
+    from: u64,
+    to: u64,
+    length: u64,
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "file {}: {} -> {} ({} blocks)",
+            self.id, self.from, self.to, self.length
+        )
+    }
+}
+
+// How a whole file picks among the free spans to its left.
+#[derive(Debug, Clone, Copy)]
+enum Fit {
+    First,
+    Best,
+    Worst,
+}
+
+impl Fit {
+    // Each heap holds the positions of the free spans of one size, so its
+    // top is the leftmost span of that size. Returns (start, size).
+    fn choose(
+        &self,
+        free: &[BinaryHeap<Reverse<u64>>],
+        size: u64,
+        position: u64,
+    ) -> Option<(u64, usize)> {
+        let mut spans = (size as usize..=MAX_SPAN)
+            .filter_map(|size| free[size].peek().map(|Reverse(start)| (*start, size)))
+            .filter(|(start, _)| *start < position);
+        match self {
+            Fit::First => spans.min(),
+            Fit::Best => spans.next(),
+            Fit::Worst => spans.next_back(),
+        }
+    }
 }
 
-fn allocate(index: u32, size: u32) -> Block {
-    assert!(index % 2 == 0);
+fn allocate(index: u64, size: u64) -> Block {
+    assert!(index.is_multiple_of(2));
     Block {
         id: Some(index / 2),
         size,
     }
 }
-fn reserve(size: u32) -> Block {
+fn reserve(size: u64) -> Block {
     Block { id: None, size }
 }
 
@@ -21,24 +75,76 @@ fn is_file(block: &Block) -> bool {
     block.id.is_some()
 }
 
-fn _print_diskmap(diskmap: &VecDeque<Block>) {
-    let mut s: String;
-    for block in diskmap {
-        if block.id.is_none() {
-            s = ".".repeat(block.size as usize);
-        } else {
-            s = (block.id.unwrap().to_string()).repeat(block.size as usize);
+// Draws one cell per block, each file id padded to the width of the largest
+// id so that neighbouring files with multi-digit ids can't run together.
+fn render_diskmap(diskmap: &VecDeque<Block>) -> String {
+    let largest = diskmap
+        .iter()
+        .filter_map(|block| block.id)
+        .max()
+        .unwrap_or(0);
+    let width = largest.to_string().len();
+    let cells: Vec<String> = diskmap
+        .iter()
+        .flat_map(|block| {
+            let cell = match block.id {
+                None => ".".repeat(width),
+                Some(id) => format!("{:>width$}", id),
+            };
+            std::iter::repeat_n(cell, block.size as usize)
+        })
+        .collect();
+    cells.join(" ")
+}
+
+fn cells(diskmap: &VecDeque<Block>) -> Vec<Option<u64>> {
+    diskmap
+        .iter()
+        .flat_map(|block| std::iter::repeat_n(block.id, block.size as usize))
+        .collect()
+}
+
+fn blocks(cells: &[Option<u64>]) -> VecDeque<Block> {
+    cells
+        .chunk_by(|a, b| a == b)
+        .map(|run| Block {
+            id: run[0],
+            size: run.len() as u64,
+        })
+        .collect()
+}
+
+// Applies the logged moves to the original disk, checking that every move
+// takes cells of the right file and puts them into free space. A file is
+// lifted before it is dropped, so it may slide over its own cells.
+fn replay(diskmap: &VecDeque<Block>, moves: &[Move]) -> Option<VecDeque<Block>> {
+    let mut cells = cells(diskmap);
+    for step in moves {
+        let from = step.from as usize..(step.from + step.length) as usize;
+        let to = step.to as usize..(step.to + step.length) as usize;
+        if !cells
+            .get(from.clone())?
+            .iter()
+            .all(|&id| id == Some(step.id))
+        {
+            return None;
+        }
+        cells[from].fill(None);
+        if !cells.get(to.clone())?.iter().all(Option::is_none) {
+            return None;
         }
-        print!("{}", s);
+        cells[to].fill(Some(step.id));
     }
-    println!()
+    Some(blocks(&cells))
 }
 
-// Pop the next allocated block from the diskmap, discarding free blocks
-fn pop_back(diskmap: &mut VecDeque<Block>) -> Option<Block> {
+// Pop the next allocated block from the diskmap, discarding free blocks.
+// `end` tracks the position just past the last block on the diskmap.
+fn pop_back(diskmap: &mut VecDeque<Block>, end: &mut u64) -> Option<Block> {
     if let Some(block) = diskmap.pop_back() {
+        *end -= block.size;
         if block.id.is_none() {
-            return pop_back(diskmap);
+            return pop_back(diskmap, end);
         } else {
             return Some(block);
         }
@@ -46,46 +152,48 @@ fn pop_back(diskmap: &mut VecDeque<Block>) -> Option<Block> {
     None
 }
 
-fn find_free_block_position(diskmap: &VecDeque<Block>, size: u32, limit: usize) -> Option<usize> {
-    for (i, block) in diskmap.iter().enumerate() {
-        if i >= limit {
-            return None;
-        }
-        if block.id.is_none() && block.size >= size {
-            return Some(i);
+// Sums id x position over every block, a whole block at a time. Returns
+// None if the sum overflows.
+fn checksum(compact: &VecDeque<Block>) -> Option<u64> {
+    let mut position: u64 = 0;
+    let mut sum: u64 = 0;
+    for block in compact {
+        let end = position.checked_add(block.size)?;
+        if let (Some(id), 1..) = (block.id, block.size) {
+            // position + (position + 1) + ... + (end - 1)
+            let ends = position.checked_add(end)?.checked_sub(1)?;
+            let positions = ends.checked_mul(block.size)? / 2;
+            sum = sum.checked_add(id.checked_mul(positions)?)?;
         }
+        position = end;
     }
-    None
-}
-
-fn remaining_free_blocks(diskmap: &VecDeque<Block>, before: usize) -> bool {
-    (0..before).any(|i| diskmap[i].id.is_none())
-}
-
-fn checksum(compact: &VecDeque<Block>) -> u64 {
-    compact
-        .iter()
-        .flat_map(|b| {
-            std::iter::repeat(b.id.unwrap_or_default())
-                .take(b.size as usize)
-                .collect::<Vec<u32>>()
-        })
-        .enumerate()
-        .map(|(i, d)| (i as u32 * d) as u64)
-        .sum()
+    Some(sum)
 }
 
-fn solve_part1(diskmap: &mut VecDeque<Block>) -> u64 {
+// Moves single blocks from the end of the disk into the leftmost free space.
+fn compact_blocks(diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> VecDeque<Block> {
+    let mut diskmap = diskmap.clone();
     let mut compact: VecDeque<Block> = VecDeque::default();
+    let mut start = 0;
+    let mut end = diskmap.iter().map(|block| block.size).sum();
 
     while !diskmap.is_empty() {
         let block = diskmap.pop_front().unwrap();
         if is_file(&block) {
+            start += block.size;
             compact.push_back(block);
         } else {
             let mut free_block = block;
             let available_space = free_block.size;
-            if let Some(mut last) = pop_back(diskmap) {
+            if let Some(mut last) = pop_back(&mut diskmap, &mut end) {
+                let length = available_space.min(last.size);
+                log.push(Move {
+                    id: last.id.unwrap(),
+                    from: end + last.size - length,
+                    to: start,
+                    length,
+                });
+                start += length;
                 match available_space.cmp(&last.size) {
                     std::cmp::Ordering::Equal => {
                         // free space matches allocated block size
@@ -99,6 +207,7 @@ fn solve_part1(diskmap: &mut VecDeque<Block>) -> u64 {
                         free_block.id = last.id;
                         last.size -= available_space;
                         compact.push_back(free_block);
+                        end += last.size;
                         diskmap.push_back(last);
                     }
                     std::cmp::Ordering::Greater => {
@@ -114,63 +223,345 @@ fn solve_part1(diskmap: &mut VecDeque<Block>) -> u64 {
             }
         }
     }
+    compact
+}
 
-    checksum(&compact)
+fn solve_part1(diskmap: &VecDeque<Block>) -> Option<u64> {
+    checksum(&compact_blocks(diskmap, &mut Vec::new()))
 }
 
-fn solve_part2(diskmap: &mut VecDeque<Block>) -> u64 {
-    let mut current_block_index = diskmap.len() - 1;
-    while remaining_free_blocks(diskmap, current_block_index) {
-        while diskmap[current_block_index].id.is_none() {
-            current_block_index -= 1;
+// Moves whole files, highest id first, into a free span to their left
+// chosen by `fit`. Free spans are kept in a min-heap of positions per span
+// size, so finding a span only means peeking at one heap per size.
+fn compact_files(diskmap: &VecDeque<Block>, fit: Fit, log: &mut Vec<Move>) -> VecDeque<Block> {
+    let mut free: [BinaryHeap<Reverse<u64>>; MAX_SPAN + 1] = Default::default();
+    let mut files: Vec<(u64, Block)> = Vec::new();
+    let mut position = 0;
+    for block in diskmap {
+        if is_file(block) {
+            files.push((position, *block));
+        } else if block.size > 0 {
+            free[block.size as usize].push(Reverse(position));
         }
-        let allocated_block = diskmap[current_block_index];
-        if let Some(free_index) =
-            find_free_block_position(diskmap, allocated_block.size, current_block_index)
-        {
-            let free_block = diskmap[free_index];
-            match free_block.size.cmp(&allocated_block.size) {
-                std::cmp::Ordering::Equal => {
-                    diskmap[free_index].id = allocated_block.id;
-                    diskmap[current_block_index].id = None;
-                }
-                std::cmp::Ordering::Less => {
-                    panic!("free block is smaller than allocated block");
-                }
-                std::cmp::Ordering::Greater => {
-                    // move allocated block to free block
-                    // mark allocated block as free
-                    // create new free block with remaining free space
-                    let extra_space = free_block.size - allocated_block.size;
-                    diskmap[free_index].id = allocated_block.id;
-                    diskmap[free_index].size = allocated_block.size;
-                    diskmap[current_block_index].id = None;
-                    diskmap.insert(free_index + 1, reserve(extra_space));
-                }
+        position += block.size;
+    }
+
+    for (position, file) in files.iter_mut().rev() {
+        if let Some((start, size)) = fit.choose(&free, file.size, *position) {
+            free[size].pop();
+            let remaining = size - file.size as usize;
+            if remaining > 0 {
+                free[remaining].push(Reverse(start + file.size));
             }
+            log.push(Move {
+                id: file.id.unwrap(),
+                from: *position,
+                to: start,
+                length: file.size,
+            });
+            *position = start;
         }
-        current_block_index -= 1;
     }
-    checksum(diskmap)
+
+    layout(files)
 }
 
-fn main() {
-    let digits: Vec<u32> = include_str!("puzzle.txt")
-        .trim()
-        .chars()
-        .map(|c| c.to_digit(10).unwrap())
-        .collect();
+// Lays out files at their positions, with free blocks filling the gaps.
+fn layout(mut files: Vec<(u64, Block)>) -> VecDeque<Block> {
+    files.sort_by_key(|(position, _)| *position);
+    let mut compact = VecDeque::new();
+    let mut end = 0;
+    for (position, file) in files {
+        if position > end {
+            compact.push_back(reserve(position - end));
+        }
+        compact.push_back(file);
+        end = position + file.size;
+    }
+    compact
+}
+
+fn solve_part2(diskmap: &VecDeque<Block>) -> Option<u64> {
+    checksum(&compact_files(diskmap, Fit::First, &mut Vec::new()))
+}
 
+// The number of free gaps left between files.
+fn fragmentation(compact: &VecDeque<Block>) -> usize {
+    let runs = blocks(&cells(compact));
+    let last_file = runs.iter().rposition(is_file).unwrap_or(0);
+    runs.iter()
+        .take(last_file)
+        .filter(|block| !is_file(block))
+        .count()
+}
+
+fn parse_diskmap(digits: &str) -> VecDeque<Block> {
     let mut diskmap: VecDeque<Block> = VecDeque::default();
 
-    for (i, digit) in digits.iter().enumerate() {
+    for (i, c) in digits.trim().chars().enumerate() {
+        let digit = c.to_digit(10).unwrap() as u64;
         if i % 2 == 0 {
-            diskmap.push_back(allocate(i as u32, *digit));
+            diskmap.push_back(allocate(i as u64, digit));
         } else {
-            diskmap.push_back(reserve(*digit));
+            diskmap.push_back(reserve(digit));
         }
     }
+    diskmap
+}
 
-    println!("Part 1: {:?}", solve_part1(&mut diskmap.clone()));
-    println!("Part 2: {:?}", solve_part2(&mut diskmap));
+// Removes `flag` from args, reporting whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let found = args.iter().position(|arg| arg == flag);
+    found.inspect(|&i| {
+        args.remove(i);
+    });
+    found.is_some()
+}
+
+// Removes `option` and the value following it from args.
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == option)?;
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+fn main() {
+    // cargo run -- [--example | --synthetic <digits>] [render | strategies | log <strategy>]
+    // The synthetic disk map is all 9s, large enough to overflow 32-bit ids
+    // and positions.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let input = if take_flag(&mut args, "--example") {
+        include_str!("example.txt").to_string()
+    } else if let Some(digits) = take_option(&mut args, "--synthetic") {
+        "9".repeat(digits.parse().unwrap())
+    } else {
+        include_str!("puzzle.txt").to_string()
+    };
+    let diskmap = parse_diskmap(&input);
+
+    let strategies = strategy::strategies();
+    let find = |name: &str| {
+        strategies
+            .iter()
+            .find(|strategy| strategy.name() == name)
+            .unwrap_or_else(|| panic!("Unknown strategy: {}", name))
+    };
+
+    match args.first().map(String::as_str) {
+        Some("render") => {
+            println!("{}", render_diskmap(&diskmap));
+            for strategy in &strategies {
+                match strategy.compact(&diskmap, &mut Vec::new()) {
+                    Some(compact) => println!("{}", render_diskmap(&compact)),
+                    None => println!("{}: gave up", strategy.name()),
+                }
+            }
+        }
+        Some("log") => {
+            let strategy = find(args.get(1).map_or("blocks", String::as_str));
+            let mut log = Vec::new();
+            let Some(compact) = strategy.compact(&diskmap, &mut log) else {
+                eprintln!("{}: gave up", strategy.name());
+                std::process::exit(1);
+            };
+            log.iter().for_each(|step| println!("{}", step));
+            match replay(&diskmap, &log) {
+                Some(replayed) => println!(
+                    "Replayed {} moves: checksum {:?}, expected {:?}",
+                    log.len(),
+                    checksum(&replayed),
+                    checksum(&compact)
+                ),
+                None => {
+                    eprintln!("{}: the log does not replay", strategy.name());
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("strategies") => {
+            for strategy in &strategies {
+                let mut log = Vec::new();
+                match strategy.compact(&diskmap, &mut log) {
+                    Some(compact) => println!(
+                        "{}: checksum {:?}, {} moves, {} gaps",
+                        strategy.name(),
+                        checksum(&compact),
+                        log.len(),
+                        fragmentation(&compact)
+                    ),
+                    None => println!("{}: gave up", strategy.name()),
+                }
+            }
+        }
+        _ => {
+            println!("Part 1: {:?}", solve_part1(&diskmap).unwrap());
+            println!("Part 2: {:?}", solve_part2(&diskmap).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strategy::{CompactionStrategy, FewestMoves, GapFree};
+
+    fn example() -> VecDeque<Block> {
+        parse_diskmap(include_str!("example.txt"))
+    }
+
+    #[test]
+    fn example_answers() {
+        let diskmap = example();
+        assert_eq!(solve_part1(&diskmap), Some(1928));
+        assert_eq!(solve_part2(&diskmap), Some(2858));
+    }
+
+    #[test]
+    fn strategy_moves_and_gaps_on_example() {
+        let expected = [
+            ("blocks", 7, 0),
+            ("first-fit", 4, 5),
+            ("best-fit", 4, 5),
+            ("worst-fit", 4, 5),
+            ("gap-free", 7, 0),
+            ("fewest-moves", 6, 0),
+        ];
+        let diskmap = example();
+        let strategies = strategy::strategies();
+        assert_eq!(strategies.len(), expected.len());
+        for (strategy, (name, moves, gaps)) in strategies.iter().zip(expected) {
+            let mut log = Vec::new();
+            let compact = strategy.compact(&diskmap, &mut log).unwrap();
+            assert_eq!(strategy.name(), name);
+            assert_eq!(
+                (log.len(), fragmentation(&compact)),
+                (moves, gaps),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn fewest_moves_beats_the_greedy_sweep() {
+        // 001111.22.3...4444 compacts in two moves: file 2 goes after file 3,
+        // then file 4 fills the hole file 2 left. Sweeping left to right slides
+        // files along one by one instead.
+        let diskmap = parse_diskmap("204121134");
+        let mut fewest = Vec::new();
+        let compact = FewestMoves.compact(&diskmap, &mut fewest).unwrap();
+        assert_eq!(fragmentation(&compact), 0);
+        assert_eq!(
+            fewest
+                .iter()
+                .map(|step| (step.id, step.from, step.to))
+                .collect::<Vec<_>>(),
+            [(2, 7, 11), (4, 14, 6)]
+        );
+        let mut sweep = Vec::new();
+        GapFree.compact(&diskmap, &mut sweep).unwrap();
+        assert!(sweep.len() > fewest.len());
+    }
+
+    #[test]
+    fn fewest_moves_gives_up_on_the_puzzle() {
+        let diskmap = parse_diskmap(include_str!("puzzle.txt"));
+        assert!(FewestMoves.compact(&diskmap, &mut Vec::new()).is_none());
+    }
+
+    #[test]
+    fn replaying_each_log_reproduces_the_layout() {
+        let trimmed = |diskmap: &VecDeque<Block>| {
+            let mut cells = cells(diskmap);
+            while cells.last() == Some(&None) {
+                cells.pop();
+            }
+            cells
+        };
+        for diskmap in [example(), parse_diskmap("204121134")] {
+            for strategy in strategy::strategies() {
+                let mut log = Vec::new();
+                let compact = strategy.compact(&diskmap, &mut log).unwrap();
+                let replayed = replay(&diskmap, &log).unwrap();
+                assert_eq!(trimmed(&replayed), trimmed(&compact), "{}", strategy.name());
+                assert_eq!(
+                    checksum(&replayed),
+                    checksum(&compact),
+                    "{}",
+                    strategy.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn replay_rejects_bad_moves() {
+        // 0..111....
+        let diskmap = parse_diskmap("1234");
+        let step = |id, from, to, length| Move {
+            id,
+            from,
+            to,
+            length,
+        };
+        assert!(replay(&diskmap, &[step(1, 3, 1, 3)]).is_some());
+        assert!(replay(&diskmap, &[step(0, 3, 1, 3)]).is_none());
+        assert!(replay(&diskmap, &[step(1, 3, 0, 3)]).is_none());
+        assert!(replay(&diskmap, &[step(1, 3, 9, 3)]).is_none());
+    }
+
+    #[test]
+    fn render_pads_ids_to_the_widest() {
+        // Files 0 to 10, one block each, with a free block between each pair.
+        // Every cell is two characters wide plus a separator, so 10 can't be
+        // mistaken for files 1 and 0.
+        let rendered = render_diskmap(&parse_diskmap(&"1".repeat(21))) + " ";
+        let cells: Vec<&str> = (0..rendered.len())
+            .step_by(3)
+            .map(|i| &rendered[i..i + 3])
+            .collect();
+        assert_eq!(cells.len(), 21);
+        assert!(cells.iter().all(|cell| cell.ends_with(' ')));
+        assert_eq!(cells[..3], [" 0 ", ".. ", " 1 "]);
+        assert_eq!(cells[20], "10 ");
+    }
+
+    // 50,000 files of 9 blocks, each followed by 9 free blocks.
+    const FILES: u128 = 50_000;
+
+    fn synthetic() -> VecDeque<Block> {
+        parse_diskmap(&"9".repeat(2 * FILES as usize))
+    }
+
+    // Sums id x position one cell at a time, in u128 so it can't overflow.
+    fn naive_checksum(diskmap: &VecDeque<Block>) -> u128 {
+        cells(diskmap)
+            .iter()
+            .enumerate()
+            .filter_map(|(position, id)| id.map(|id| id as u128 * position as u128))
+            .sum()
+    }
+
+    #[test]
+    fn synthetic_checksum_matches_closed_form() {
+        // File k fills 18k..18k+9, adding k * (162k + 36). Summed over k < n
+        // that is 162 * n(n-1)(2n-1)/6 + 36 * n(n-1)/2.
+        let n = FILES;
+        let expected = 27 * n * (n - 1) * (2 * n - 1) + 18 * n * (n - 1);
+        assert!(expected > u32::MAX as u128);
+        assert_eq!(checksum(&synthetic()).map(u128::from), Some(expected));
+    }
+
+    #[test]
+    fn synthetic_compactions_match_naive_checksum() {
+        let diskmap = synthetic();
+        let compacted = [
+            compact_blocks(&diskmap, &mut Vec::new()),
+            compact_files(&diskmap, Fit::First, &mut Vec::new()),
+        ];
+        for compact in compacted {
+            let expected = naive_checksum(&compact);
+            assert!(expected > u32::MAX as u128);
+            assert_eq!(checksum(&compact).map(u128::from), Some(expected));
+        }
+    }
 }
@@ -0,0 +1,358 @@
+use crate::{compact_blocks, compact_files, is_file, layout, Block, Fit, Move, MAX_SPAN};
+use std::collections::VecDeque;
+
+pub trait CompactionStrategy {
+    fn name(&self) -> &str;
+    // Returns the compacted disk, logging every move made along the way, or
+    // None if the strategy gives up on this disk.
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>>;
+}
+
+// Part 1: single blocks from the end fill the leftmost free space.
+pub struct BlockLevel;
+
+// Part 2: whole files, highest id first, move to the leftmost span that fits.
+pub struct FirstFit;
+
+// Whole files move to the smallest span that fits, leaving the least waste.
+pub struct BestFit;
+
+// Whole files move to the largest span, leaving the most room for others.
+pub struct WorstFit;
+
+// Whole files move so that no gaps are left, each file at most once. This is
+// a greedy sweep, so it keeps the number of moves low without promising the
+// fewest possible.
+pub struct GapFree;
+
+// Whole files move so that no gaps are left, in the fewest moves possible
+// when no file may move twice. The search is exhaustive, so it gives up on
+// disks much bigger than the example.
+pub struct FewestMoves;
+
+// Search steps FewestMoves may take before giving up.
+const SEARCH_BUDGET: usize = 1_000_000;
+
+impl CompactionStrategy for BlockLevel {
+    fn name(&self) -> &str {
+        "blocks"
+    }
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>> {
+        Some(compact_blocks(diskmap, log))
+    }
+}
+
+impl CompactionStrategy for FirstFit {
+    fn name(&self) -> &str {
+        "first-fit"
+    }
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>> {
+        Some(compact_files(diskmap, Fit::First, log))
+    }
+}
+
+impl CompactionStrategy for BestFit {
+    fn name(&self) -> &str {
+        "best-fit"
+    }
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>> {
+        Some(compact_files(diskmap, Fit::Best, log))
+    }
+}
+
+impl CompactionStrategy for WorstFit {
+    fn name(&self) -> &str {
+        "worst-fit"
+    }
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>> {
+        Some(compact_files(diskmap, Fit::Worst, log))
+    }
+}
+
+impl CompactionStrategy for GapFree {
+    fn name(&self) -> &str {
+        "gap-free"
+    }
+    // With no gaps left the files fill exactly the first `total` cells, so
+    // every file reaching past that point has to move at least once. Sweeping
+    // left to right, each gap is filled exactly with such files when their
+    // sizes add up, and otherwise the next file slides left to close it.
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>> {
+        let mut files: Vec<(u64, Block)> = Vec::new();
+        let mut position = 0;
+        for block in diskmap {
+            if is_file(block) {
+                files.push((position, *block));
+            }
+            position += block.size;
+        }
+        let total: u64 = files.iter().map(|(_, file)| file.size).sum();
+
+        // Files that have to move anyway, by size and in disk order.
+        let mut spare: [VecDeque<usize>; MAX_SPAN + 1] = Default::default();
+        for (i, (position, file)) in files.iter().enumerate() {
+            if file.size > 0 && position + file.size > total {
+                spare[file.size as usize].push_back(i);
+            }
+        }
+
+        let mut moved = vec![false; files.len()];
+        let mut cursor = 0;
+        for i in 0..files.len() {
+            let (position, file) = files[i];
+            if moved[i] {
+                continue;
+            }
+            if file.size == 0 {
+                files[i].0 = cursor;
+                continue;
+            }
+            if spare[file.size as usize].front() == Some(&i) {
+                spare[file.size as usize].pop_front();
+            }
+            if let Some(sizes) = exact_fill(&spare, position - cursor) {
+                for size in sizes {
+                    let j = spare[size].pop_back().unwrap();
+                    relocate(&mut files[j], cursor, log);
+                    moved[j] = true;
+                    cursor += size as u64;
+                }
+            }
+            if cursor != position {
+                relocate(&mut files[i], cursor, log);
+            }
+            cursor += file.size;
+        }
+        Some(layout(files))
+    }
+}
+
+impl CompactionStrategy for FewestMoves {
+    fn name(&self) -> &str {
+        "fewest-moves"
+    }
+    // A file that never moves must already lie within the first `total`
+    // cells, and the files that do move must exactly fill the holes between
+    // those that stay. Trying ever larger sets of files to evict from that
+    // region, the first set whose holes can be filled, in an order where
+    // every destination is free by the time its file moves, keeps the most
+    // files in place. Evicting everything always succeeds, as the files can
+    // then slide left in disk order.
+    fn compact(&self, diskmap: &VecDeque<Block>, log: &mut Vec<Move>) -> Option<VecDeque<Block>> {
+        let mut files: Vec<(u64, Block)> = Vec::new();
+        let mut position = 0;
+        for block in diskmap {
+            if is_file(block) {
+                files.push((position, *block));
+            }
+            position += block.size;
+        }
+        let total = files.iter().map(|(_, file)| file.size).sum();
+        let candidates: Vec<usize> = (0..files.len())
+            .filter(|&i| files[i].1.size > 0 && files[i].0 + files[i].1.size <= total)
+            .collect();
+
+        let mut search = Search {
+            files: &files,
+            total,
+            budget: SEARCH_BUDGET,
+        };
+        let plan = (0..=candidates.len())
+            .find_map(|count| {
+                search
+                    .evict(&candidates, count, &mut Vec::new())
+                    .transpose()
+            })?
+            .ok()?;
+        for (i, to) in plan {
+            relocate(&mut files[i], to, log);
+        }
+        Some(layout(files))
+    }
+}
+
+// Files to move, with their destinations, in the order they move.
+type Plan = Vec<(usize, u64)>;
+
+struct OutOfBudget;
+
+struct Search<'a> {
+    files: &'a [(u64, Block)],
+    total: u64,
+    budget: usize,
+}
+
+impl Search<'_> {
+    fn spend(&mut self, steps: usize) -> Result<(), OutOfBudget> {
+        self.budget = self.budget.checked_sub(steps).ok_or(OutOfBudget)?;
+        Ok(())
+    }
+
+    // Tries every way of evicting `count` of the candidates.
+    fn evict(
+        &mut self,
+        candidates: &[usize],
+        count: usize,
+        evicted: &mut Vec<usize>,
+    ) -> Result<Option<Plan>, OutOfBudget> {
+        if count == 0 {
+            return self.fill(evicted);
+        }
+        for k in 0..=candidates.len().saturating_sub(count) {
+            evicted.push(candidates[k]);
+            let plan = self.evict(&candidates[k + 1..], count - 1, evicted)?;
+            evicted.pop();
+            if plan.is_some() {
+                return Ok(plan);
+            }
+        }
+        Ok(None)
+    }
+
+    // Works out the holes left by the files that stay, then tries to fill
+    // them with the rest.
+    fn fill(&mut self, evicted: &[usize]) -> Result<Option<Plan>, OutOfBudget> {
+        let mut holes = Vec::new();
+        let mut pieces = Vec::new();
+        let mut cursor = 0;
+        for (i, (position, file)) in self.files.iter().enumerate() {
+            if file.size == 0 {
+                continue;
+            }
+            if position + file.size > self.total || evicted.contains(&i) {
+                pieces.push(i);
+                continue;
+            }
+            if *position > cursor {
+                holes.push((cursor, *position));
+            }
+            cursor = position + file.size;
+        }
+        if cursor < self.total {
+            holes.push((cursor, self.total));
+        }
+        let start = holes.first().map_or(0, |hole| hole.0);
+        let mut used = vec![false; pieces.len()];
+        self.pack(&holes, start, &pieces, &mut used, &mut Vec::new())
+    }
+
+    // Fills the holes left to right, trying every unused piece at each spot.
+    fn pack(
+        &mut self,
+        holes: &[(u64, u64)],
+        cursor: u64,
+        pieces: &[usize],
+        used: &mut [bool],
+        placed: &mut Plan,
+    ) -> Result<Option<Plan>, OutOfBudget> {
+        let Some(&(_, end)) = holes.first() else {
+            return self.order(placed);
+        };
+        if cursor == end {
+            let next = holes.get(1).map_or(0, |hole| hole.0);
+            return self.pack(&holes[1..], next, pieces, used, placed);
+        }
+        // Pieces lying wholly past the compacted region never stand in the
+        // way of another move, so any two of the same size are interchangeable.
+        let mut tried = Vec::new();
+        for (k, &i) in pieces.iter().enumerate() {
+            self.spend(1)?;
+            let (origin, file) = self.files[i];
+            if used[k] || cursor + file.size > end {
+                continue;
+            }
+            if origin >= self.total {
+                if tried.contains(&file.size) {
+                    continue;
+                }
+                tried.push(file.size);
+            }
+            used[k] = true;
+            placed.push((i, cursor));
+            let plan = self.pack(holes, cursor + file.size, pieces, used, placed)?;
+            placed.pop();
+            used[k] = false;
+            if plan.is_some() {
+                return Ok(plan);
+            }
+        }
+        Ok(None)
+    }
+
+    // Orders the moves so each file's destination has been vacated by the
+    // time it moves. A file may land on its own old cells. Returns None when
+    // the moves wait on each other in a cycle.
+    fn order(&mut self, placed: &Plan) -> Result<Option<Plan>, OutOfBudget> {
+        self.spend(placed.len() * placed.len())?;
+        let overlaps = |a: (u64, u64), b: (u64, u64)| a.0 < b.1 && b.0 < a.1;
+        let span = |start: u64, i: usize| (start, start + self.files[i].1.size);
+        let mut waiting: Vec<usize> = placed
+            .iter()
+            .map(|&(i, to)| {
+                placed
+                    .iter()
+                    .filter(|&&(j, _)| j != i && overlaps(span(to, i), span(self.files[j].0, j)))
+                    .count()
+            })
+            .collect();
+        let mut done = vec![false; placed.len()];
+        let mut plan = Vec::new();
+        while let Some(k) = (0..placed.len()).find(|&k| !done[k] && waiting[k] == 0) {
+            done[k] = true;
+            let (j, _) = placed[k];
+            plan.push(placed[k]);
+            for (other, &(i, to)) in placed.iter().enumerate() {
+                if i != j && overlaps(span(to, i), span(self.files[j].0, j)) {
+                    waiting[other] -= 1;
+                }
+            }
+        }
+        Ok((plan.len() == placed.len()).then_some(plan))
+    }
+}
+
+fn relocate((position, file): &mut (u64, Block), to: u64, log: &mut Vec<Move>) {
+    log.push(Move {
+        id: file.id.unwrap(),
+        from: *position,
+        to,
+        length: file.size,
+    });
+    *position = to;
+}
+
+// The sizes of the fewest spare files that exactly fill a gap, if any.
+fn exact_fill(spare: &[VecDeque<usize>], gap: u64) -> Option<Vec<usize>> {
+    let gap = gap as usize;
+    let mut fewest: Vec<Option<Vec<usize>>> = vec![None; gap + 1];
+    fewest[0] = Some(Vec::new());
+    for size in 1..=MAX_SPAN {
+        for _ in 0..spare[size].len().min(gap / size) {
+            for sum in (size..=gap).rev() {
+                let Some(sizes) = &fewest[sum - size] else {
+                    continue;
+                };
+                if fewest[sum]
+                    .as_ref()
+                    .is_none_or(|best| best.len() > sizes.len() + 1)
+                {
+                    let mut sizes = sizes.clone();
+                    sizes.push(size);
+                    fewest[sum] = Some(sizes);
+                }
+            }
+        }
+    }
+    fewest[gap].take()
+}
+
+pub fn strategies() -> Vec<Box<dyn CompactionStrategy>> {
+    vec![
+        Box::new(BlockLevel),
+        Box::new(FirstFit),
+        Box::new(BestFit),
+        Box::new(WorstFit),
+        Box::new(GapFree),
+        Box::new(FewestMoves),
+    ]
+}